@@ -0,0 +1,49 @@
+//! Compares `Interner` with `LocalInterner` on a single thread. Run with `cargo bench`.
+
+extern crate interner;
+
+use std::time::{Duration, Instant};
+
+use interner::{Interner, KeyDigest, LocalInterner};
+
+const WORDS: usize = 1000;
+const ROUNDS: usize = 100;
+
+fn words() -> Vec<String> {
+    (0..WORDS).map(|i| format!("word{}", i % (WORDS / 4))).collect()
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    // Warm up, then take the best of a few runs.
+    f();
+    (0..5).map(|_| {
+        let start = Instant::now();
+        f();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    let words = words();
+
+    // A fast digest, so that the cost of hashing doesn't drown out the difference.
+    let interner = Interner::builder().key_digest(KeyDigest::Fast128).build();
+    let shared = time(|| {
+        for _ in 0..ROUNDS {
+            let handles: Vec<_> = words.iter().map(|w| interner.intern_borrowed(&w[..])).collect();
+            drop(handles);
+        }
+    });
+
+    let interner = LocalInterner::<String>::with_options(None, KeyDigest::Fast128);
+    let local = time(|| {
+        for _ in 0..ROUNDS {
+            let handles: Vec<_> = words.iter().map(|w| interner.intern_borrowed(&w[..])).collect();
+            drop(handles);
+        }
+    });
+
+    let per_op = |d: Duration| d.as_nanos() as f64 / (WORDS * ROUNDS) as f64;
+    println!("Interner:      {:.1} ns/intern", per_op(shared));
+    println!("LocalInterner: {:.1} ns/intern", per_op(local));
+}
@@ -0,0 +1,133 @@
+//! Per-entry access statistics, for seeing which values are hot and which are dead weight.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use {extend_lifetime, BuildKeyHasher, BuildMap, InternError, InternKey, Interned, Interner, MapBackend};
+
+/// How an entry has been used since it was inserted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccessStats {
+    /// How many handles have been taken to the entry, counting the one taken when it was
+    /// inserted but not clones of existing handles.
+    pub hits: u64,
+    /// When a handle was last taken to the entry.
+    pub last_access: Instant,
+}
+
+pub(crate) type AccessTable<const N: usize> = Mutex<HashMap<InternKey<N>, AccessStats, BuildKeyHasher>>;
+
+/// A handle to a hot entry, with its statistics.
+type HotEntry<'a, T, S, const N: usize> = (Interned<'a, T, S, N>, AccessStats);
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Called whenever a handle is taken to an entry.
+    pub(crate) fn record_access(&self, key: &InternKey<N>) {
+        if let Some(ref table) = self.access_stats {
+            let now = Instant::now();
+            let mut table = table.lock().unwrap();
+            let stats = table.entry(key.clone()).or_insert(AccessStats {
+                hits: 0,
+                last_access: now,
+            });
+            stats.hits += 1;
+            stats.last_access = now;
+        }
+    }
+
+    /// Called for each removed entry, so that a re-interned value starts afresh.
+    pub(crate) fn forget_access(&self, key: &InternKey<N>) {
+        if let Some(ref table) = self.access_stats {
+            table.lock().unwrap().remove(key);
+        }
+    }
+
+    /// The access statistics of every entry, sorted by key. Empty unless the interner was
+    /// built with `InternerBuilder::access_stats`. Only keys are returned, since taking
+    /// handles would count as accesses.
+    pub fn access_stats(&self) -> Vec<(InternKey<N>, AccessStats)> {
+        let mut stats: Vec<_> = match self.access_stats {
+            Some(ref table) => table.lock().unwrap().iter().map(|(key, stats)| (key.clone(), *stats)).collect(),
+            None => Vec::new(),
+        };
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// Handles to the `n` entries with the most hits, hottest first, for deciding what's
+    /// worth preloading. Ties are broken by key. Taking these handles doesn't count as an
+    /// access. Empty unless the interner was built with `InternerBuilder::access_stats`.
+    pub fn hottest<'a>(&'a self, n: usize) -> Result<Vec<HotEntry<'a, T, S, N>>, InternError>
+            where T: 'a
+    {
+        let mut stats = self.access_stats();
+        stats.sort_by(|a, b| b.1.hits.cmp(&a.1.hits).then_with(|| a.0.cmp(&b.0)));
+        let mut hottest = Vec::with_capacity(cmp::min(n, stats.len()));
+        for (key, stats) in stats {
+            if hottest.len() == n {
+                break;
+            }
+            let map = try!(self.read(&key));
+            // The entry may have been removed since the stats were read.
+            if let Some(field) = map.get(&key) {
+                field.count.fetch_add(1, Relaxed);
+                let field = unsafe { extend_lifetime(field) };
+                hottest.push((self.wrap(field), stats));
+            }
+        }
+        Ok(hottest)
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// The entry's access statistics, if its interner records them.
+    pub fn access_stats(&self) -> Option<AccessStats> {
+        match self.interner.access_stats {
+            Some(ref table) => table.lock().unwrap().get(self.key()).cloned(),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn access_stats() {
+        let interner = Interner::builder().access_stats().build();
+        let a = interner.intern(String::from("a"));
+        let first = a.access_stats().unwrap();
+        assert_eq!(first.hits, 1);
+        let _ = a.clone();
+        for _ in 0..3 {
+            drop(interner.intern_borrowed("a"));
+        }
+        let b = interner.intern(String::from("b"));
+        let stats = a.access_stats().unwrap();
+        assert_eq!(stats.hits, 4);
+        assert!(stats.last_access >= first.last_access);
+        let all = interner.access_stats();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|&(ref key, stats)| key == b.key() && stats.hits == 1));
+
+        drop(b);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.access_stats().len(), 1);
+        assert_eq!(Interner::<String>::new().intern(String::from("a")).access_stats(), None);
+    }
+
+    #[test]
+    fn hottest() {
+        let interner = Interner::builder().access_stats().build();
+        let _words: Vec<_> = "a b a c a b".split(' ').map(|w| interner.intern_borrowed(w)).collect();
+        let hottest: Vec<_> = interner.hottest(2).unwrap().into_iter().map(|(w, stats)| ((*w).clone(), stats.hits)).collect();
+        assert_eq!(hottest, vec![(String::from("a"), 3), (String::from("b"), 2)]);
+        assert_eq!(interner.hottest(10).unwrap().len(), 3);
+        assert_eq!(interner.access_stats().iter().map(|s| s.1.hits).sum::<u64>(), 6);
+        assert!(Interner::<String>::new().hottest(1).unwrap().is_empty());
+    }
+}
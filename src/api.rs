@@ -0,0 +1,107 @@
+//! The interner's operations as a trait, so code can be written against any interner
+//! implementation (eg. a counting or failing fake in tests).
+
+use std::hash::Hash;
+use std::ops::Deref;
+
+use {BuildMap, InternError, Interned, Interner};
+
+/// The core interner operations. `'a` is the lifetime of the borrow of the interner that
+/// handles are tied to, which lets this be used as a trait object, eg.
+/// `&dyn InternerApi<'a, T, Handle=Interned<'a, T>>`.
+pub trait InternerApi<'a, T> {
+    type Handle: Deref<Target=T> + Clone;
+
+    fn intern(&'a self, data: T) -> Self::Handle;
+
+    fn try_intern(&'a self, data: T) -> Result<Self::Handle, InternError>;
+
+    /// Get a handle to the value equal to `data` if one is already interned.
+    fn get(&'a self, data: &T) -> Option<Self::Handle>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a, T: Hash + 'a, S: BuildMap<T, N> + 'a, const N: usize> InternerApi<'a, T> for Interner<T, S, N> {
+    type Handle = Interned<'a, T, S, N>;
+
+    fn intern(&'a self, data: T) -> Interned<'a, T, S, N> {
+        Interner::intern(self, data)
+    }
+
+    /// Unlike `Interner::try_intern` this doesn't require `T: Eq`, and so doesn't detect key
+    /// collisions.
+    fn try_intern(&'a self, data: T) -> Result<Interned<'a, T, S, N>, InternError> {
+        let key = self.key_of(&data);
+        self.insert_or_get(key, data, |_, _| true)
+    }
+
+    fn get(&'a self, data: &T) -> Option<Interned<'a, T, S, N>> {
+        Interner::get(self, data)
+    }
+
+    fn len(&self) -> usize {
+        Interner::len(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::InternerApi;
+    use {Interned, Interner, InternError};
+
+    struct Counting<'a> {
+        inner: &'a Interner<String>,
+        calls: Cell<usize>,
+    }
+
+    impl<'a> InternerApi<'a, String> for Counting<'a> {
+        type Handle = Interned<'a, String>;
+
+        fn intern(&'a self, data: String) -> Interned<'a, String> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.intern(data)
+        }
+
+        fn try_intern(&'a self, _: String) -> Result<Interned<'a, String>, InternError> {
+            Err(InternError::CapacityExceeded)
+        }
+
+        fn get(&'a self, data: &String) -> Option<Interned<'a, String>> {
+            self.inner.get(data)
+        }
+
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+    }
+
+    fn intern_words<'a>(api: &'a dyn InternerApi<'a, String, Handle=Interned<'a, String>>)
+        -> Vec<Interned<'a, String>>
+    {
+        vec![api.intern(String::from("a")), api.intern(String::from("b")), api.intern(String::from("a"))]
+    }
+
+    #[test]
+    fn real_and_fake() {
+        let interner = Interner::new();
+        let words = intern_words(&interner);
+        assert_eq!(words[0], words[2]);
+        assert_eq!(InternerApi::len(&interner), 2);
+        assert!(InternerApi::get(&interner, &String::from("b")).is_some());
+
+        let fake = Counting {
+            inner: &interner,
+            calls: Cell::new(0),
+        };
+        let _ = intern_words(&fake);
+        assert_eq!(fake.calls.get(), 3);
+        assert_eq!(fake.try_intern(String::from("c")), Err(InternError::CapacityExceeded));
+    }
+}
@@ -0,0 +1,58 @@
+//! Tables of `Arc`s, for callers who want owned handles to interned data that aren't tied
+//! to the interner's lifetime.
+
+use std::sync::Arc;
+
+use {BuildMap, Interned, Interner};
+
+impl<'a, U: ?Sized, S: BuildMap<Arc<U>, N>, const N: usize> Interned<'a, Arc<U>, S, N> {
+    /// Get an owned pointer to the interned data without copying it.
+    pub fn to_arc(&self) -> Arc<U> {
+        (**self).clone()
+    }
+}
+
+impl<S: BuildMap<Arc<str>, N>, const N: usize> Interner<Arc<str>, S, N> {
+    /// Intern a string, returning a clone of the shared `Arc` it's stored in. The returned
+    /// `Arc` doesn't keep the entry alive, so once all `Interned` handles are dropped and the
+    /// entry is reclaimed, interning the same string again allocates a new `Arc`.
+    pub fn intern_arc_str(&self, data: &str) -> Arc<str> {
+        self.intern_borrowed_with(data, |data| Arc::from(data)).to_arc()
+    }
+}
+
+impl<S: BuildMap<Arc<[u8]>, N>, const N: usize> Interner<Arc<[u8]>, S, N> {
+    /// Intern a byte string, returning a clone of the shared `Arc` it's stored in. See
+    /// `intern_arc_str`.
+    pub fn intern_arc_bytes(&self, data: &[u8]) -> Arc<[u8]> {
+        self.intern_borrowed_with(data, |data| Arc::from(data)).to_arc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use Interner;
+
+    #[test]
+    fn arc_str() {
+        let interner = Interner::new();
+        let held = interner.intern_borrowed_with("hello", |s| Arc::<str>::from(s));
+        let a = interner.intern_arc_str("hello");
+        let b = interner.intern_arc_str("hello");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(Arc::ptr_eq(&a, &held.to_arc()));
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || tx.send(a).unwrap());
+        assert_eq!(&*rx.recv().unwrap(), "hello");
+
+        let bytes = Interner::new();
+        let x = bytes.intern(Arc::from(&b"xyz"[..]));
+        let y = bytes.intern_arc_bytes(b"xyz");
+        assert!(Arc::ptr_eq(&x.to_arc(), &y));
+    }
+}
@@ -0,0 +1,161 @@
+//! Pluggable storage for the shards of an interner's table.
+//!
+//! An interner's `S` parameter chooses its storage. Any `BuildHasher` stores entries in a
+//! `HashMap` hashed with it, or implement `BuildMap` to use some other map.
+
+use std::collections::{hash_map, BTreeMap, HashMap};
+use std::collections::btree_map;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+use {InternField, InternKey};
+
+/// One shard of an interner's table. The interner guards each shard with a lock: `get`,
+/// `iter` and `len` are called with it held shared, the rest with it held exclusively. So an
+/// implementation needn't be thread-safe itself.
+///
+/// # Safety
+///
+/// Handles point into the boxed entries, so an entry must stay in its box, and the box
+/// must not be dropped, until `remove` hands it back. `get` and `get_or_insert` must return
+/// the entry stored under the key they were given, and `iter` must only yield stored
+/// entries. The interner only removes entries which have no handles.
+pub unsafe trait MapBackend<T, const N: usize = 20> {
+    fn get(&self, key: &InternKey<N>) -> Option<&InternField<T, N>>;
+
+    /// Insert `field` under its key unless there's already an entry there. Returns the
+    /// entry now under the key, and `field` back if it wasn't inserted.
+    fn get_or_insert(&mut self, field: Box<InternField<T, N>>)
+        -> (&InternField<T, N>, Option<Box<InternField<T, N>>>);
+
+    fn remove(&mut self, key: &InternKey<N>) -> Option<Box<InternField<T, N>>>;
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=&'a InternField<T, N>> + 'a>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Makes the `MapBackend` for each shard of an interner, like `BuildHasher` makes hashers.
+pub trait BuildMap<T, const N: usize = 20> {
+    type Map: MapBackend<T, N>;
+
+    /// Make an empty shard with room for at least `capacity` entries. Every shard is made
+    /// from the same `this`, which shards can keep a reference to.
+    fn build_map(this: &Arc<Self>, capacity: usize) -> Self::Map;
+}
+
+unsafe impl<T, S: BuildHasher, const N: usize> MapBackend<T, N> for HashMap<InternKey<N>, Box<InternField<T, N>>, S> {
+    fn get(&self, key: &InternKey<N>) -> Option<&InternField<T, N>> {
+        HashMap::get(self, key).map(|field| &**field)
+    }
+
+    fn get_or_insert(&mut self, field: Box<InternField<T, N>>)
+        -> (&InternField<T, N>, Option<Box<InternField<T, N>>>)
+    {
+        match self.entry(field.key.clone()) {
+            hash_map::Entry::Occupied(oe) => (oe.into_mut(), Some(field)),
+            hash_map::Entry::Vacant(ve) => (ve.insert(field), None),
+        }
+    }
+
+    fn remove(&mut self, key: &InternKey<N>) -> Option<Box<InternField<T, N>>> {
+        HashMap::remove(self, key)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=&'a InternField<T, N>> + 'a> {
+        Box::new(self.values().map(|field| &**field))
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// The `BuildHasher` of each shard stored in a `HashMap`: the interner's hasher, shared
+/// between shards so that it needn't be `Clone`.
+#[derive(Debug)]
+pub struct SharedHasher<S>(Arc<S>);
+
+impl<S> Clone for SharedHasher<S> {
+    fn clone(&self) -> SharedHasher<S> {
+        SharedHasher(self.0.clone())
+    }
+}
+
+impl<S: BuildHasher> BuildHasher for SharedHasher<S> {
+    type Hasher = S::Hasher;
+
+    fn build_hasher(&self) -> S::Hasher {
+        self.0.build_hasher()
+    }
+}
+
+impl<T, S: BuildHasher, const N: usize> BuildMap<T, N> for S {
+    type Map = HashMap<InternKey<N>, Box<InternField<T, N>>, SharedHasher<S>>;
+
+    fn build_map(this: &Arc<S>, capacity: usize) -> Self::Map {
+        HashMap::with_capacity_and_hasher(capacity, SharedHasher(this.clone()))
+    }
+}
+
+/// Stores each shard in a `BTreeMap`, which keeps entries in key order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BTreeBackend;
+
+unsafe impl<T, const N: usize> MapBackend<T, N> for BTreeMap<InternKey<N>, Box<InternField<T, N>>> {
+    fn get(&self, key: &InternKey<N>) -> Option<&InternField<T, N>> {
+        BTreeMap::get(self, key).map(|field| &**field)
+    }
+
+    fn get_or_insert(&mut self, field: Box<InternField<T, N>>)
+        -> (&InternField<T, N>, Option<Box<InternField<T, N>>>)
+    {
+        match self.entry(field.key.clone()) {
+            btree_map::Entry::Occupied(oe) => (oe.into_mut(), Some(field)),
+            btree_map::Entry::Vacant(ve) => (ve.insert(field), None),
+        }
+    }
+
+    fn remove(&mut self, key: &InternKey<N>) -> Option<Box<InternField<T, N>>> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=&'a InternField<T, N>> + 'a> {
+        Box::new(self.values().map(|field| &**field))
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+}
+
+impl<T, const N: usize> BuildMap<T, N> for BTreeBackend {
+    type Map = BTreeMap<InternKey<N>, Box<InternField<T, N>>>;
+
+    fn build_map(_this: &Arc<BTreeBackend>, _capacity: usize) -> Self::Map {
+        BTreeMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BTreeBackend;
+    use Interner;
+
+    #[test]
+    fn btree_backend() {
+        let interner = Interner::builder().backend(BTreeBackend).shards(1).build();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        assert_eq!(interner.intern_borrowed("a"), a);
+        assert_eq!(interner.len(), 2);
+        drop(a);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.get("b"), Some(b));
+    }
+}
@@ -0,0 +1,344 @@
+//! Fluent configuration of an `Interner`.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+
+use {default_shard_count, next_interner_id, phf, BuildKeyHasher, BuildMap, Counters, EvictionPolicy,
+     EvictionReason, InconsistencyPolicy, Interner, KeyDigest, Leak, LeakHook, LockStrategy, Retired, Salt, ShardStrategy, StableIds, Symbols,
+     ADAPTIVE_INITIAL_SPINS};
+#[cfg(feature = "debug-handles")]
+use Origins;
+
+/// Collects an interner's options. Start with `Interner::builder()` and finish with `build()`.
+pub struct InternerBuilder<T, S = BuildKeyHasher, const N: usize = 20> {
+    salt: Option<Salt>,
+    export_salt: Option<Salt>,
+    digest: KeyDigest,
+    build_map: S,
+    shards: usize,
+    shard_strategy: ShardStrategy,
+    lock_strategy: LockStrategy,
+    capacity: usize,
+    heap_size: Option<fn(&T) -> usize>,
+    stable_ids: bool,
+    symbols: bool,
+    access_stats: bool,
+    perfect_hash: Option<Vec<Vec<u8>>>,
+    mark_and_sweep: bool,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    on_evict: Option<Box<dyn Fn(T, EvictionReason) + Send + Sync>>,
+    eviction_policy: Option<Box<dyn EvictionPolicy<N> + Send>>,
+    inconsistency: InconsistencyPolicy,
+    on_leak: Option<LeakHook<T, N>>,
+}
+
+impl<T> Interner<T> {
+    pub fn builder() -> InternerBuilder<T> {
+        Interner::builder_with_key_bytes()
+    }
+
+    /// Like `builder`, for an interner whose keys are `M` bytes wide rather than the default
+    /// 20. `M` must be a multiple of 4 from 8 to 32. Narrower keys save memory in each entry,
+    /// but make collisions likelier. The width is chosen up front because the eviction
+    /// policy and `on_leak` callback work with keys of a particular width.
+    pub fn builder_with_key_bytes<const M: usize>() -> InternerBuilder<T, BuildKeyHasher, M> {
+        InternerBuilder {
+            salt: None,
+            export_salt: None,
+            digest: Default::default(),
+            build_map: Default::default(),
+            shards: default_shard_count(),
+            shard_strategy: Default::default(),
+            lock_strategy: Default::default(),
+            capacity: 0,
+            heap_size: None,
+            stable_ids: false,
+            symbols: false,
+            access_stats: false,
+            perfect_hash: None,
+            mark_and_sweep: false,
+            max_entries: None,
+            max_bytes: None,
+            on_evict: None,
+            eviction_policy: None,
+            inconsistency: Default::default(),
+            on_leak: None,
+        }
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> InternerBuilder<T, S, N> {
+    /// Derive keys using `salt`. See `Interner::with_salt`.
+    pub fn salt(mut self, salt: Salt) -> InternerBuilder<T, S, N> {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Mix `salt` into keys when they're shown outside the interner: in dumps, exports, deltas
+    /// and handles' `Debug` output. Otherwise exported keys are plain content hashes, and
+    /// anyone who can guess a short value (a name, say) can confirm it was interned by
+    /// hashing it themselves. Keys used internally are unaffected. See
+    /// `Interner::exported_key`.
+    pub fn export_salt(mut self, salt: Salt) -> InternerBuilder<T, S, N> {
+        self.export_salt = Some(salt);
+        self
+    }
+
+    /// Derive keys using a freshly generated random salt.
+    pub fn random_salt(self) -> InternerBuilder<T, S, N> {
+        self.salt(Salt::random())
+    }
+
+    /// Derive keys with `digest`. Defaults to `KeyDigest::Sha1`.
+    pub fn key_digest(mut self, digest: KeyDigest) -> InternerBuilder<T, S, N> {
+        self.digest = digest;
+        self
+    }
+
+    /// Hash keys in the internal map using `hash_builder`.
+    pub fn hasher<S2: BuildHasher>(self, hash_builder: S2) -> InternerBuilder<T, S2, N> {
+        self.backend(hash_builder)
+    }
+
+    /// Store the table's shards in the maps made by `build_map`.
+    pub fn backend<S2: BuildMap<T, N>>(self, build_map: S2) -> InternerBuilder<T, S2, N> {
+        InternerBuilder {
+            salt: self.salt,
+            export_salt: self.export_salt,
+            digest: self.digest,
+            build_map: build_map,
+            shards: self.shards,
+            shard_strategy: self.shard_strategy,
+            lock_strategy: self.lock_strategy,
+            capacity: self.capacity,
+            heap_size: self.heap_size,
+            stable_ids: self.stable_ids,
+            symbols: self.symbols,
+            access_stats: self.access_stats,
+            perfect_hash: self.perfect_hash,
+            mark_and_sweep: self.mark_and_sweep,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            on_evict: self.on_evict,
+            eviction_policy: self.eviction_policy,
+            inconsistency: self.inconsistency,
+            on_leak: self.on_leak,
+        }
+    }
+
+    /// Split the table into `shards` separately locked shards, rounded up to a power of two.
+    /// Defaults to `default_shard_count()`.
+    pub fn shards(mut self, shards: usize) -> InternerBuilder<T, S, N> {
+        self.shards = shards;
+        self
+    }
+
+    /// Choose how keys are assigned to shards.
+    pub fn shard_strategy(mut self, strategy: ShardStrategy) -> InternerBuilder<T, S, N> {
+        self.shard_strategy = strategy;
+        self
+    }
+
+    /// Choose what threads do when a shard's lock is held. Defaults to blocking.
+    pub fn lock_strategy(mut self, strategy: LockStrategy) -> InternerBuilder<T, S, N> {
+        self.lock_strategy = strategy;
+        self
+    }
+
+    /// Reserve room for `capacity` entries up front.
+    pub fn capacity(mut self, capacity: usize) -> InternerBuilder<T, S, N> {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Measure the heap memory owned by each value with `heap_size`, for the byte counts in
+    /// `Interner::stats` and the `max_bytes` limit. Otherwise only the entries themselves are counted.
+    pub fn heap_size(mut self, heap_size: fn(&T) -> usize) -> InternerBuilder<T, S, N> {
+        self.heap_size = Some(heap_size);
+        self
+    }
+
+    /// Assign each key a `StableId` when it's first interned. The ID table is never
+    /// shrunk, so it holds one key for every distinct value the interner has ever seen.
+    pub fn stable_ids(mut self) -> InternerBuilder<T, S, N> {
+        self.stable_ids = true;
+        self
+    }
+
+    /// Give each entry a `Symbol` while it's in the table.
+    pub fn symbols(mut self) -> InternerBuilder<T, S, N> {
+        self.symbols = true;
+        self
+    }
+
+    /// Record each entry's `AccessStats`. This takes a lock on every lookup.
+    pub fn access_stats(mut self) -> InternerBuilder<T, S, N> {
+        self.access_stats = true;
+        self
+    }
+
+    /// Never remove entries as their handles are dropped, only when `Interner::collect` is
+    /// called. Dropping the last handle to an entry then does nothing but decrement its count.
+    ///
+    /// This makes the interner a cache: a value that's interned, dropped and interned again
+    /// is found rather than rebuilt. Unused entries are kept until `collect` or
+    /// `Interner::sweep_stale` is called, or until `max_entries`, `max_bytes` or an
+    /// `eviction_policy` needs the room.
+    pub fn mark_and_sweep(mut self) -> InternerBuilder<T, S, N> {
+        self.mark_and_sweep = true;
+        self
+    }
+
+    /// Hold at most `max_entries` entries. Interning a new value into a full interner first
+    /// reclaims entries whose handles have all been dropped, then evicts any other entries
+    /// without handles (such as those kept in mark-and-sweep mode). If there's still no room,
+    /// `try_intern` and the other fallible methods fail with `InternError::CapacityExceeded`,
+    /// but `intern` and everything built on it, such as `Rodeo`, `LocalCache` and the
+    /// `InternDeep` impls, panic. Use the fallible methods on an interner which can fill up.
+    pub fn max_entries(mut self, max_entries: usize) -> InternerBuilder<T, S, N> {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Hold at most `max_bytes` bytes of entries, as measured for `Stats::bytes`: each entry's
+    /// own size plus whatever the `heap_size` function reports for its value. Without a
+    /// `heap_size` function only the entries themselves count. Otherwise this works like
+    /// `max_entries`, and the two can be combined.
+    pub fn max_bytes(mut self, max_bytes: usize) -> InternerBuilder<T, S, N> {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Call `on_evict` with each entry that's evicted, along with the reason, rather than just
+    /// dropping it. Entries are evicted by `Interner::sweep_stale` and to make room under
+    /// `max_entries` or `max_bytes`, but not when they're removed because their last handle
+    /// was dropped. It's called without any locks held.
+    pub fn on_evict<F>(mut self, on_evict: F) -> InternerBuilder<T, S, N>
+            where F: Fn(T, EvictionReason) + Send + Sync + 'static
+    {
+        self.on_evict = Some(Box::new(on_evict));
+        self
+    }
+
+    /// Evict entries in the order chosen by `policy` when making room under `max_entries` or
+    /// `max_bytes`, rather than evicting every entry without handles at once. The policy is
+    /// told about every insert, lookup and removal, under a lock of its own.
+    pub fn eviction_policy<P>(mut self, policy: P) -> InternerBuilder<T, S, N>
+            where P: EvictionPolicy<N> + Send + 'static
+    {
+        self.eviction_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Choose what happens when a dropped handle turns out not to be in the table. Defaults
+    /// to `InconsistencyPolicy::DebugAssert`, so that release builds don't abort over it.
+    pub fn on_inconsistency(mut self, policy: InconsistencyPolicy) -> InternerBuilder<T, S, N> {
+        self.inconsistency = policy;
+        self
+    }
+
+    /// When the interner is dropped, pass every entry that still has handles to `report`,
+    /// sorted by key. It isn't called if there are none. A handle can only outlive its
+    /// interner by being leaked, so this is for finding handle leaks.
+    pub fn on_leak<F>(mut self, report: F) -> InternerBuilder<T, S, N>
+            where F: Fn(Vec<Leak<N>>) + Send + Sync + 'static,
+                  T: fmt::Debug
+    {
+        self.on_leak = Some(LeakHook::new(report));
+        self
+    }
+
+    /// Precompute the keys of `values` in a perfect hash table, which is checked before
+    /// deriving a key. Interning or looking up one of these values then costs a cheap hash
+    /// of the bytes it hashes as rather than a digest. Values which hash as more than 64
+    /// bytes are left out.
+    pub fn perfect_hash<I>(mut self, values: I) -> InternerBuilder<T, S, N>
+            where I: IntoIterator<Item=T>,
+                  T: Hash
+    {
+        self.perfect_hash = Some(values.into_iter().filter_map(|value| phf::record(&value)).collect());
+        self
+    }
+
+    pub fn build(self) -> Interner<T, S, N> {
+        let shards = cmp::max(self.shards, 1).next_power_of_two();
+        let per_shard = self.capacity.div_ceil(shards);
+        let build_map = Arc::new(self.build_map);
+        let mut interner = Interner {
+            shards: (0..shards).map(|_| RwLock::new(BuildMap::build_map(&build_map, per_shard)))
+                               .collect::<Vec<_>>()
+                               .into_boxed_slice(),
+            shard_strategy: self.shard_strategy,
+            lock_strategy: self.lock_strategy,
+            adaptive_spins: AtomicUsize::new(ADAPTIVE_INITIAL_SPINS),
+            retired: Retired::new(),
+            salt: match self.digest {
+                KeyDigest::Sip128 => Some(self.salt.unwrap_or_else(Salt::random)),
+                _ => self.salt,
+            },
+            export_salt: self.export_salt,
+            digest: self.digest,
+            counters: Counters::new(),
+            heap_size: self.heap_size,
+            stable_ids: if self.stable_ids { Some(StableIds::new()) } else { None },
+            symbols: if self.symbols { Some(Symbols::new()) } else { None },
+            access_stats: if self.access_stats { Some(Mutex::new(HashMap::default())) } else { None },
+            epoch: AtomicU64::new(0),
+            mark_and_sweep: self.mark_and_sweep,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            on_evict: self.on_evict,
+            eviction_policy: self.eviction_policy.map(Mutex::new),
+            inconsistency: self.inconsistency,
+            on_leak: self.on_leak,
+            #[cfg(feature = "debug-handles")]
+            origins: Origins::new(),
+            id: next_interner_id(),
+            perfect_hash: None,
+        };
+        if let Some(streams) = self.perfect_hash {
+            interner.build_perfect_hash(streams);
+        }
+        interner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use {Interner, LockStrategy, Lru, ShardStrategy};
+
+    #[test]
+    fn builder() {
+        let interner = Interner::builder()
+                                .random_salt()
+                                .hasher(RandomState::new())
+                                .shards(2)
+                                .shard_strategy(ShardStrategy::Head)
+                                .lock_strategy(LockStrategy::Spin(100))
+                                .capacity(100)
+                                .build();
+        let a = interner.intern(String::from("a"));
+        assert_eq!(a, interner.intern_borrowed("a"));
+        assert!(interner.salt.is_some());
+        assert_eq!(interner.shards.len(), 2);
+        assert!(interner.shards[0].read().unwrap().capacity() >= 50);
+    }
+    #[test]
+    fn key_width_and_eviction_policy() {
+        let interner = Interner::builder_with_key_bytes::<8>()
+                                .eviction_policy(Lru::new())
+                                .max_entries(1)
+                                .build();
+        drop(interner.intern(String::from("a")));
+        drop(interner.intern(String::from("b")));
+        assert_eq!(interner.len(), 1);
+        assert!(interner.get("b").is_some());
+    }
+}
@@ -0,0 +1,106 @@
+//! A per-thread front cache, so that hot values can be re-interned without touching the
+//! interner's shared lock.
+
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner};
+
+/// Remembers handles to recently interned values. Hits only cost a hash and a count bump,
+/// misses go through to the interner. Cached handles keep their entries alive until the
+/// cache is cleared or dropped.
+pub struct LocalCache<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    capacity: usize,
+    handles: RefCell<HashMap<InternKey<N>, Interned<'a, T, S, N>, BuildKeyHasher>>,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Create a front cache for use on the current thread, holding up to `capacity` handles.
+    pub fn local_cache<'a>(&'a self, capacity: usize) -> LocalCache<'a, T, S, N> {
+        LocalCache {
+            interner: self,
+            capacity: capacity,
+            handles: RefCell::new(HashMap::default()),
+        }
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> LocalCache<'a, T, S, N> {
+    fn intern_with<F>(&self, key: InternKey<N>, f: F) -> Interned<'a, T, S, N>
+            where F: FnOnce() -> T
+    {
+        if let Some(handle) = self.handles.borrow().get(&key) {
+            return handle.clone();
+        }
+        let handle = self.interner.intern_with(key.clone(), f);
+        let evicted = {
+            let mut handles = self.handles.borrow_mut();
+            // No eviction order is tracked, so a full cache is just flushed.
+            let evicted = if handles.len() >= self.capacity {
+                handles.drain().map(|(_, handle)| handle).collect()
+            } else {
+                Vec::new()
+            };
+            if self.capacity > 0 {
+                handles.insert(key, handle.clone());
+            }
+            evicted
+        };
+        drop(evicted);
+        handle
+    }
+
+    pub fn intern(&self, data: T) -> Interned<'a, T, S, N>
+            where T: Hash
+    {
+        let key = self.interner.key_of(&data);
+        self.intern_with(key, || data)
+    }
+
+    pub fn intern_borrowed<B>(&self, data: &B) -> Interned<'a, T, S, N>
+            where B: ?Sized + Hash + ToOwned<Owned=T>,
+                  T: Borrow<B>
+    {
+        let key = self.interner.key_of(data);
+        self.intern_with(key, || data.to_owned())
+    }
+
+    /// The number of handles currently cached.
+    pub fn len(&self) -> usize {
+        self.handles.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all cached handles.
+    pub fn clear(&self) {
+        let handles: Vec<_> = self.handles.borrow_mut().drain().map(|(_, handle)| handle).collect();
+        drop(handles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn local_cache() {
+        let interner: Interner<String> = Interner::new();
+        let cache = interner.local_cache(2);
+        let a = cache.intern_borrowed("a");
+        let a2 = cache.intern(String::from("a"));
+        assert_eq!(a, a2);
+        assert_eq!(cache.len(), 1);
+        let _b = cache.intern_borrowed("b");
+        let _c = cache.intern_borrowed("c");
+        assert_eq!(cache.len(), 1);
+        assert_eq!(a, interner.intern_borrowed("a"));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! A content-addressable store on disk, for payloads too large to keep interned all the time.
+//! Blobs are written to a directory under their keys, and only the ones in use need to be
+//! resident in the interner.
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner};
+
+/// A directory of blobs named by their keys, backed by an interner. `put` interns a blob and
+/// spills it to disk, and `get_by_key` finds it again, from the interner if it's resident
+/// and from disk if not. The `CasDir` keeps a handle to each resident blob, so blobs stay in
+/// memory until they're `evict`ed.
+///
+/// Blobs are stored as their bytes, so the interner's keys must be reproducible across
+/// processes (ie. unsalted, or salted the same) for a directory to be reopened.
+pub struct CasDir<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    dir: PathBuf,
+    resident: Mutex<HashMap<InternKey<N>, Interned<'a, T, S, N>, BuildKeyHasher>>,
+}
+
+impl<'a, T, S, const N: usize> CasDir<'a, T, S, N>
+        where T: Hash + AsRef<[u8]> + From<Vec<u8>> + 'a,
+              S: BuildMap<T, N> + 'a
+{
+    /// Use `dir` as a store for `interner`'s blobs, creating it if need be. Blobs already in
+    /// the directory are found by `get_by_key` but aren't loaded until they're asked for.
+    pub fn open<P: AsRef<Path>>(interner: &'a Interner<T, S, N>, dir: P) -> io::Result<CasDir<'a, T, S, N>> {
+        try!(fs::create_dir_all(dir.as_ref()));
+        Ok(CasDir {
+            interner: interner,
+            dir: dir.as_ref().to_path_buf(),
+            resident: Mutex::new(HashMap::default()),
+        })
+    }
+
+    fn path(&self, key: &InternKey<N>) -> PathBuf {
+        self.dir.join(key.to_hex())
+    }
+
+    fn make_resident(&self, handle: &Interned<'a, T, S, N>) {
+        let mut resident = self.resident.lock().unwrap();
+        if !resident.contains_key(handle.key()) {
+            resident.insert(handle.key().clone(), handle.clone());
+        }
+    }
+
+    /// Intern `data`, writing it to the directory if it isn't there already. Files are
+    /// written and synced under a temporary name unique to the process and the call, then
+    /// renamed into place, so a crash never leaves a truncated blob under a key. A blob put
+    /// by someone else in the meantime is as good as this one, since it has the same bytes.
+    pub fn put(&self, data: T) -> io::Result<Interned<'a, T, S, N>> {
+        static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+        let handle = self.interner.intern(data);
+        let path = self.path(handle.key());
+        if !path.exists() {
+            let tmp = self.dir.join(format!("{}.{}.{}.tmp",
+                                            handle.key().to_hex(),
+                                            process::id(),
+                                            NEXT_TMP.fetch_add(1, Relaxed)));
+            let written = write_synced(&tmp, (*handle).as_ref()).and_then(|()| fs::rename(&tmp, &path));
+            if let Err(e) = written {
+                let _ = fs::remove_file(&tmp);
+                if !path.exists() {
+                    return Err(e);
+                }
+            }
+        }
+        self.make_resident(&handle);
+        Ok(handle)
+    }
+
+    /// The blob stored under `key`, loading it back into the interner if it isn't resident.
+    /// A file whose contents don't hash to its name is reported as `InvalidData`.
+    pub fn get_by_key(&self, key: &InternKey<N>) -> io::Result<Option<Interned<'a, T, S, N>>> {
+        if let Some(handle) = self.resident.lock().unwrap().get(key) {
+            return Ok(Some(handle.clone()));
+        }
+        let bytes = match fs::read(self.path(key)) {
+            Ok(bytes) => bytes,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let handle = self.interner.intern(T::from(bytes));
+        if handle.key() != key {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("blob {} is corrupt", key.to_hex())));
+        }
+        self.make_resident(&handle);
+        Ok(Some(handle))
+    }
+
+    /// Whether there's a blob stored under `key`, resident or not.
+    pub fn contains_key(&self, key: &InternKey<N>) -> bool {
+        self.resident.lock().unwrap().contains_key(key) || self.path(key).exists()
+    }
+
+    /// Whether the blob under `key` is held in memory by this `CasDir`.
+    pub fn is_resident(&self, key: &InternKey<N>) -> bool {
+        self.resident.lock().unwrap().contains_key(key)
+    }
+
+    /// Release this `CasDir`'s handle to the blob under `key`, returning whether it was
+    /// resident. The blob stays on disk, and leaves the interner once any other handles to
+    /// it are gone and it's reclaimed.
+    pub fn evict(&self, key: &InternKey<N>) -> bool {
+        self.resident.lock().unwrap().remove(key).is_some()
+    }
+
+    /// Release every resident blob. See `evict`.
+    pub fn evict_all(&self) {
+        self.resident.lock().unwrap().clear();
+    }
+
+    /// The number of blobs held in memory by this `CasDir`.
+    pub fn resident_len(&self) -> usize {
+        self.resident.lock().unwrap().len()
+    }
+
+    /// The keys of every blob in the directory, sorted.
+    pub fn keys(&self) -> io::Result<Vec<InternKey<N>>> {
+        let mut keys = Vec::new();
+        for entry in try!(fs::read_dir(&self.dir)) {
+            let entry = try!(entry);
+            if let Some(key) = entry.file_name().to_str().and_then(InternKey::from_hex) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = try!(fs::File::create(path));
+    try!(file.write_all(bytes));
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::CasDir;
+    use Interner;
+
+    #[test]
+    fn spill_and_reload() {
+        let dir = env::temp_dir().join(format!("interner-cas-{}", process::id()));
+        let interner: Interner<Vec<u8>> = Interner::new();
+        let key = {
+            let cas = CasDir::open(&interner, &dir).unwrap();
+            let blob = cas.put(vec![7; 4096]).unwrap();
+            assert_eq!(cas.put(vec![7; 4096]).unwrap(), blob);
+            // No temporary files are left behind.
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+            assert_eq!(cas.keys().unwrap(), vec![blob.key().clone()]);
+            assert!(cas.evict(blob.key()));
+            assert!(!cas.is_resident(blob.key()));
+            assert!(cas.contains_key(blob.key()));
+            blob.key().clone()
+        };
+        interner.reclaim().unwrap();
+        assert!(interner.is_empty());
+
+        let cas = CasDir::open(&interner, &dir).unwrap();
+        let blob = cas.get_by_key(&key).unwrap().unwrap();
+        assert_eq!(*blob, vec![7; 4096]);
+        assert!(cas.is_resident(&key));
+        assert_eq!(interner.len(), 1);
+
+        fs::write(dir.join(key.to_hex()), b"tampered").unwrap();
+        cas.evict_all();
+        drop(blob);
+        interner.reclaim().unwrap();
+        assert!(cas.get_by_key(&key).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//! Interning `Cow`s, and interning into tables of `Cow`s so that data that's already
+//! `'static` can be stored by reference instead of being copied.
+
+use std::borrow::{Borrow, Cow};
+use std::hash::Hash;
+
+use {BuildMap, Intern, Interned, Interner};
+
+impl<T: Hash, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Intern data that may or may not be owned. Owned data is moved into the table if it
+    /// isn't interned yet, borrowed data is only copied in that case, and nothing is
+    /// allocated if it's already interned.
+    pub fn intern_cow<'a, 'b, B>(&'a self, data: Cow<'b, B>) -> Interned<'a, T, S, N>
+            where B: ?Sized + Hash + ToOwned<Owned=T>,
+                  T: Borrow<B> + 'a
+    {
+        match data {
+            Cow::Borrowed(data) => self.intern_borrowed(data),
+            Cow::Owned(data) => self.intern(data),
+        }
+    }
+}
+
+impl<'b> Intern<String> for Cow<'b, str> {
+    fn intern_in<'a, S: BuildMap<String, N>, const N: usize>(self, interner: &'a Interner<String, S, N>) -> Interned<'a, String, S, N>
+            where String: 'a
+    {
+        interner.intern_cow(self)
+    }
+}
+
+impl<S: BuildMap<Cow<'static, str>, N>, const N: usize> Interner<Cow<'static, str>, S, N> {
+    /// Intern a `'static` string without copying it onto the heap.
+    pub fn intern_static<'a>(&'a self, data: &'static str) -> Interned<'a, Cow<'static, str>, S, N> {
+        self.intern_borrowed_with(data, |_| Cow::Borrowed(data))
+    }
+
+    /// Intern a string of any lifetime, copying it only if it isn't interned yet.
+    pub fn intern_str<'a>(&'a self, data: &str) -> Interned<'a, Cow<'static, str>, S, N> {
+        self.intern_borrowed_with(data, |data| Cow::Owned(data.to_owned()))
+    }
+}
+
+impl<S: BuildMap<Cow<'static, [u8]>, N>, const N: usize> Interner<Cow<'static, [u8]>, S, N> {
+    /// Intern a `'static` byte string without copying it onto the heap.
+    pub fn intern_static_bytes<'a>(&'a self, data: &'static [u8]) -> Interned<'a, Cow<'static, [u8]>, S, N> {
+        self.intern_borrowed_with(data, |_| Cow::Borrowed(data))
+    }
+
+    /// Intern a byte string of any lifetime, copying it only if it isn't interned yet.
+    pub fn intern_bytes<'a>(&'a self, data: &[u8]) -> Interned<'a, Cow<'static, [u8]>, S, N> {
+        self.intern_borrowed_with(data, |data| Cow::Owned(data.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use {Intern, Interner};
+
+    #[test]
+    fn intern_cow() {
+        let interner: Interner<String> = Interner::new();
+        let owned = String::from("owned");
+        let ptr = owned.as_ptr();
+        let a = interner.intern_cow(Cow::<str>::Owned(owned));
+        assert_eq!(a.as_ptr(), ptr);
+        let b = interner.intern_cow(Cow::Borrowed("owned"));
+        assert_eq!(a, b);
+        let c = Cow::Borrowed("borrowed").intern_in(&interner);
+        assert_eq!(*c, "borrowed");
+    }
+
+    static KEYWORDS: &[&str] = &["fn", "let", "match"];
+
+    #[test]
+    fn intern_static() {
+        let interner = Interner::new();
+        let handles: Vec<_> = KEYWORDS.iter().map(|kw| interner.intern_static(kw)).collect();
+        match *handles[0] {
+            Cow::Borrowed(s) => assert_eq!(s.as_ptr(), KEYWORDS[0].as_ptr()),
+            Cow::Owned(..) => panic!("static string was copied"),
+        }
+        let dynamic = String::from("let");
+        assert_eq!(interner.intern_str(&dynamic), handles[1]);
+        assert_eq!(interner.intern(Cow::Owned(dynamic)), handles[1]);
+
+        let bytes = Interner::new();
+        let b0 = bytes.intern_static_bytes(b"abc");
+        let dynamic = String::from("abc");
+        let b1 = bytes.intern_bytes(dynamic.as_bytes());
+        assert_eq!(b0, b1);
+        match *b1 {
+            Cow::Borrowed(..) => (),
+            Cow::Owned(..) => panic!("static bytes were copied"),
+        }
+    }
+}
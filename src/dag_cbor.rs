@@ -0,0 +1,218 @@
+//! Content addressing compatible with IPLD: values are encoded as canonical DAG-CBOR and
+//! keyed by the SHA-256 of the encoding, and keys can be written out as CIDs. A value's key
+//! is then the same digest that any IPLD tool would compute for it.
+
+use std::collections::BTreeMap;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use key_text::base32;
+use {BuildMap, InternKey, Interned, Interner};
+
+/// Types with a canonical DAG-CBOR encoding.
+pub trait DagCbor {
+    /// Append the value's encoding to `out`.
+    fn write_dag_cbor(&self, out: &mut Vec<u8>);
+}
+
+/// A value's DAG-CBOR encoding.
+pub fn to_dag_cbor<T: ?Sized + DagCbor>(value: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.write_dag_cbor(&mut out);
+    out
+}
+
+/// Write the head of a data item: its major type and an argument, in the fewest bytes.
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= 0xff {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= 0xffff {
+        out.push(major | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= 0xffff_ffff {
+        out.push(major | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// Write a map with string keys, such as a struct's fields. DAG-CBOR orders keys by length
+/// and then bytewise, whatever order they're given in here.
+pub fn write_map(out: &mut Vec<u8>, mut entries: Vec<(&str, &dyn DagCbor)>) {
+    entries.sort_by(|a, b| (a.0.len(), a.0).cmp(&(b.0.len(), b.0)));
+    write_head(out, 5, entries.len() as u64);
+    for (key, value) in entries {
+        key.write_dag_cbor(out);
+        value.write_dag_cbor(out);
+    }
+}
+
+impl DagCbor for bool {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        out.push(if *self { 0xf5 } else { 0xf4 });
+    }
+}
+
+macro_rules! unsigned {
+    ($($t:ty),*) => {$(
+        impl DagCbor for $t {
+            fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+                write_head(out, 0, *self as u64);
+            }
+        }
+    )*};
+}
+
+macro_rules! signed {
+    ($($t:ty),*) => {$(
+        impl DagCbor for $t {
+            fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+                if *self >= 0 {
+                    write_head(out, 0, *self as u64);
+                } else {
+                    write_head(out, 1, !(*self as i64) as u64);
+                }
+            }
+        }
+    )*};
+}
+
+unsigned!(u8, u16, u32, u64, usize);
+signed!(i8, i16, i32, i64, isize);
+
+/// DAG-CBOR always encodes floats in 64 bits.
+impl DagCbor for f64 {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        out.push(0xfb);
+        out.extend_from_slice(&self.to_bits().to_be_bytes());
+    }
+}
+
+impl DagCbor for str {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        write_head(out, 3, self.len() as u64);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl DagCbor for String {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        self[..].write_dag_cbor(out)
+    }
+}
+
+/// `None` is encoded as null.
+impl<T: DagCbor> DagCbor for Option<T> {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        match *self {
+            Some(ref value) => value.write_dag_cbor(out),
+            None => out.push(0xf6),
+        }
+    }
+}
+
+impl<T: DagCbor> DagCbor for [T] {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        write_head(out, 4, self.len() as u64);
+        for value in self {
+            value.write_dag_cbor(out);
+        }
+    }
+}
+
+impl<T: DagCbor> DagCbor for Vec<T> {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        self[..].write_dag_cbor(out)
+    }
+}
+
+impl<T: DagCbor> DagCbor for BTreeMap<String, T> {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        write_map(out, self.iter().map(|(key, value)| (&key[..], value as &dyn DagCbor)).collect())
+    }
+}
+
+impl<'a, T: DagCbor + ?Sized> DagCbor for &'a T {
+    fn write_dag_cbor(&self, out: &mut Vec<u8>) {
+        (**self).write_dag_cbor(out)
+    }
+}
+
+/// The CIDv1 prefix for a DAG-CBOR block with a SHA-256 multihash.
+const CID_PREFIX: [u8; 4] = [0x01, 0x71, 0x12, 0x20];
+
+impl InternKey<32> {
+    /// The binary CID of the DAG-CBOR block this key addresses.
+    pub fn to_cid_bytes(&self) -> Vec<u8> {
+        let mut cid = CID_PREFIX.to_vec();
+        cid.extend_from_slice(&self.to_bytes());
+        cid
+    }
+
+    /// Read a key back from a binary CID. Returns `None` unless it's a CIDv1 of a DAG-CBOR
+    /// block with a SHA-256 multihash.
+    pub fn from_cid_bytes(cid: &[u8]) -> Option<InternKey<32>> {
+        if cid.len() != 36 || cid[..4] != CID_PREFIX {
+            return None;
+        }
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&cid[4..]);
+        Some(InternKey::from_bytes(bytes))
+    }
+
+    /// The CID in its usual text form: multibase base32, eg. `bafyrei...`.
+    pub fn to_cid(&self) -> String {
+        format!("b{}", base32(&self.to_cid_bytes()))
+    }
+}
+
+impl<T: DagCbor, S: BuildMap<T, 32>> Interner<T, S, 32> {
+    /// Intern `data` keyed by the SHA-256 of its DAG-CBOR encoding, so that its key is its
+    /// CID. These keys don't agree with the interner's `KeyDigest`, so an interner used with
+    /// this should only be used with this.
+    pub fn intern_dag_cbor<'a>(&'a self, data: T) -> Interned<'a, T, S, 32>
+            where T: 'a
+    {
+        let mut hasher = Sha256::new();
+        hasher.input(&to_dag_cbor(&data));
+        let mut bytes = [0; 32];
+        hasher.result(&mut bytes);
+        match self.insert_or_get(InternKey::from_bytes(bytes), data, |_, _| true) {
+            Ok(interned) => interned,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{to_dag_cbor, write_map};
+    use {InternKey, Interner};
+
+    #[test]
+    fn encoding() {
+        let mut out = Vec::new();
+        write_map(&mut out, vec![("bb", &vec![Some(true), None]), ("a", &1u8)]);
+        assert_eq!(out, [0xa2, 0x61, 0x61, 0x01, 0x62, 0x62, 0x62, 0x82, 0xf5, 0xf6]);
+        assert_eq!(to_dag_cbor(&-500i32), [0x39, 0x01, 0xf3]);
+        assert_eq!(to_dag_cbor(&1_000_000u32), [0x1a, 0x00, 0x0f, 0x42, 0x40]);
+    }
+
+    #[test]
+    fn cids() {
+        let interner = Interner::builder_with_key_bytes::<32>().build();
+        let empty = interner.intern_dag_cbor(BTreeMap::<String, u8>::new());
+        assert_eq!(empty.key().to_cid(), "bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua");
+        assert_eq!(InternKey::from_cid_bytes(&empty.key().to_cid_bytes()).as_ref(), Some(empty.key()));
+        assert_eq!(interner.intern_dag_cbor(BTreeMap::new()), empty);
+    }
+}
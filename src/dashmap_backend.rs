@@ -0,0 +1,71 @@
+//! Storing an interner's shards in `DashMap`s.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+
+use {extend_lifetime, BuildKeyHasher, BuildMap, InternField, InternKey, MapBackend};
+
+/// Stores each shard in a `DashMap`. The interner still guards each shard with its own lock,
+/// so concurrency comes from the shard count, as with the other backends.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DashMapBackend;
+
+// `DashMap` hands out guards rather than references. The entries are boxed and only removed
+// through `&mut self`, so a reference into a box stays valid after its guard is dropped, for
+// as long as the map is borrowed.
+unsafe impl<T, const N: usize> MapBackend<T, N> for DashMap<InternKey<N>, Box<InternField<T, N>>, BuildKeyHasher> {
+    fn get(&self, key: &InternKey<N>) -> Option<&InternField<T, N>> {
+        DashMap::get(self, key).map(|field| unsafe { extend_lifetime(&**field) })
+    }
+
+    fn get_or_insert(&mut self, field: Box<InternField<T, N>>)
+        -> (&InternField<T, N>, Option<Box<InternField<T, N>>>)
+    {
+        match self.entry(field.key.clone()) {
+            Entry::Occupied(oe) => (unsafe { extend_lifetime(&**oe.get()) }, Some(field)),
+            Entry::Vacant(ve) => (unsafe { extend_lifetime(&**ve.insert(field)) }, None),
+        }
+    }
+
+    fn remove(&mut self, key: &InternKey<N>) -> Option<Box<InternField<T, N>>> {
+        DashMap::remove(self, key).map(|(_, field)| field)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=&'a InternField<T, N>> + 'a> {
+        Box::new(DashMap::iter(self).map(|field| unsafe { extend_lifetime(&**field.value()) }))
+    }
+
+    fn len(&self) -> usize {
+        DashMap::len(self)
+    }
+}
+
+impl<T, const N: usize> BuildMap<T, N> for DashMapBackend {
+    type Map = DashMap<InternKey<N>, Box<InternField<T, N>>, BuildKeyHasher>;
+
+    fn build_map(_this: &Arc<DashMapBackend>, capacity: usize) -> Self::Map {
+        DashMap::with_capacity_and_hasher(capacity, BuildKeyHasher::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DashMapBackend;
+    use Interner;
+
+    #[test]
+    fn dashmap_backend() {
+        let interner = Interner::builder().backend(DashMapBackend).shards(2).build();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        assert_eq!(interner.intern_borrowed("a"), a);
+        assert_eq!(interner.len(), 2);
+        drop(a);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.handles_by_key().len(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+//! Interning every part of a nested value that can be interned, bottom-up, in one call.
+//!
+//! A value is interned into a context `C` which says which types are interned: it
+//! implements `InternInto<T>` for each of them. `Interner<T>` is the context for `T` alone,
+//! and a struct holding several interners can delegate to each. Containers keep their shape
+//! and have their elements interned, and plain data such as numbers is left as it is. Structs
+//! set up with `intern_deep!` are rebuilt from their interned fields and then interned
+//! themselves, so the result is hash-consed all the way up.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash};
+
+use {BuildMap, Interned, Interner};
+
+/// A context which interns values of type `T`.
+pub trait InternInto<'a, T> {
+    type Handle;
+
+    fn intern_value(&'a self, value: T) -> Self::Handle;
+}
+
+impl<'a, T: Hash + 'a, S: BuildMap<T, N> + 'a, const N: usize> InternInto<'a, T> for Interner<T, S, N> {
+    type Handle = Interned<'a, T, S, N>;
+
+    fn intern_value(&'a self, value: T) -> Interned<'a, T, S, N> {
+        self.intern(value)
+    }
+}
+
+/// Types whose internable parts can be interned into a context `C`, giving the value's
+/// hash-consed form. Implement it for a struct with `intern_deep!`.
+pub trait InternDeep<'a, C: ?Sized> {
+    type Output;
+
+    fn intern_deep(self, cx: &'a C) -> Self::Output;
+}
+
+impl<'a, C: ?Sized + InternInto<'a, String>> InternDeep<'a, C> for String {
+    type Output = C::Handle;
+
+    fn intern_deep(self, cx: &'a C) -> C::Handle {
+        cx.intern_value(self)
+    }
+}
+
+macro_rules! plain {
+    ($($t:ty),*) => {$(
+        impl<'a, C: ?Sized> InternDeep<'a, C> for $t {
+            type Output = $t;
+
+            fn intern_deep(self, _: &'a C) -> $t {
+                self
+            }
+        }
+    )*};
+}
+
+plain!((), bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl<'a, C: ?Sized, T: InternDeep<'a, C>> InternDeep<'a, C> for Option<T> {
+    type Output = Option<T::Output>;
+
+    fn intern_deep(self, cx: &'a C) -> Option<T::Output> {
+        self.map(|value| value.intern_deep(cx))
+    }
+}
+
+impl<'a, C: ?Sized, T: InternDeep<'a, C>> InternDeep<'a, C> for Box<T> {
+    type Output = Box<T::Output>;
+
+    fn intern_deep(self, cx: &'a C) -> Box<T::Output> {
+        Box::new((*self).intern_deep(cx))
+    }
+}
+
+impl<'a, C: ?Sized, T: InternDeep<'a, C>> InternDeep<'a, C> for Vec<T> {
+    type Output = Vec<T::Output>;
+
+    fn intern_deep(self, cx: &'a C) -> Vec<T::Output> {
+        self.into_iter().map(|value| value.intern_deep(cx)).collect()
+    }
+}
+
+impl<'a, C: ?Sized, T: InternDeep<'a, C>> InternDeep<'a, C> for VecDeque<T> {
+    type Output = VecDeque<T::Output>;
+
+    fn intern_deep(self, cx: &'a C) -> VecDeque<T::Output> {
+        self.into_iter().map(|value| value.intern_deep(cx)).collect()
+    }
+}
+
+/// Maps keep their keys as they are and have their values interned.
+impl<'a, C: ?Sized, K: Ord, V: InternDeep<'a, C>> InternDeep<'a, C> for BTreeMap<K, V> {
+    type Output = BTreeMap<K, V::Output>;
+
+    fn intern_deep(self, cx: &'a C) -> BTreeMap<K, V::Output> {
+        self.into_iter().map(|(key, value)| (key, value.intern_deep(cx))).collect()
+    }
+}
+
+impl<'a, C: ?Sized, K: Eq + Hash, V: InternDeep<'a, C>, H: BuildHasher + Default> InternDeep<'a, C> for HashMap<K, V, H> {
+    type Output = HashMap<K, V::Output, H>;
+
+    fn intern_deep(self, cx: &'a C) -> HashMap<K, V::Output, H> {
+        self.into_iter().map(|(key, value)| (key, value.intern_deep(cx))).collect()
+    }
+}
+
+impl<'a, C: ?Sized, A: InternDeep<'a, C>, B: InternDeep<'a, C>> InternDeep<'a, C> for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    fn intern_deep(self, cx: &'a C) -> (A::Output, B::Output) {
+        (self.0.intern_deep(cx), self.1.intern_deep(cx))
+    }
+}
+
+impl<'a, C: ?Sized, A: InternDeep<'a, C>, B: InternDeep<'a, C>, D: InternDeep<'a, C>> InternDeep<'a, C> for (A, B, D) {
+    type Output = (A::Output, B::Output, D::Output);
+
+    fn intern_deep(self, cx: &'a C) -> (A::Output, B::Output, D::Output) {
+        (self.0.intern_deep(cx), self.1.intern_deep(cx), self.2.intern_deep(cx))
+    }
+}
+
+/// Implement `InternDeep` for a struct with named fields, building a struct of the same
+/// shape out of its interned fields and interning that:
+///
+/// ```ignore
+/// intern_deep! {
+///     impl<'a, 's: 'a> InternDeep<'a, Context<'s>> for Person => PersonDeep<'s> { name, tags }
+/// }
+/// ```
+///
+/// Each listed field of `Person` is interned with its own `InternDeep` impl into the field
+/// of the same name of `PersonDeep`, and the `PersonDeep` is interned into the context, which
+/// has to implement `InternInto` for it. The output is the context's handle to it.
+///
+/// This is a macro rather than a derive, since a derive would need a proc-macro crate of its
+/// own.
+#[macro_export]
+macro_rules! intern_deep {
+    (impl<$($lt:lifetime $(: $bound:lifetime)?),*> InternDeep<$cxlt:lifetime, $cx:ty> for $from:ident => $to:ident<$($tolt:lifetime),*> {
+        $($field:ident),* $(,)*
+    }) => {
+        impl<$($lt $(: $bound)?),*> $crate::InternDeep<$cxlt, $cx> for $from {
+            type Output = <$cx as $crate::InternInto<$cxlt, $to<$($tolt),*>>>::Handle;
+
+            fn intern_deep(self, cx: &$cxlt $cx) -> Self::Output {
+                $crate::InternInto::intern_value(cx, $to {
+                    $($field: $crate::InternDeep::intern_deep(self.$field, cx),)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{InternDeep, InternInto};
+    use {Interned, Interner};
+
+    struct Person {
+        name: String,
+        age: u32,
+        tags: Vec<String>,
+        links: BTreeMap<u32, Option<String>>,
+    }
+
+    #[derive(Hash, Debug)]
+    struct PersonDeep<'s> {
+        name: Interned<'s, String>,
+        age: u32,
+        tags: Vec<Interned<'s, String>>,
+        links: BTreeMap<u32, Option<Interned<'s, String>>>,
+    }
+
+    /// Interns strings into one interner, and people made of them into another.
+    struct Context<'s> {
+        strings: &'s Interner<String>,
+        people: Interner<PersonDeep<'s>>,
+    }
+
+    impl<'a, 's> InternInto<'a, String> for Context<'s> {
+        type Handle = Interned<'s, String>;
+
+        fn intern_value(&'a self, value: String) -> Interned<'s, String> {
+            self.strings.intern(value)
+        }
+    }
+
+    impl<'a, 's: 'a> InternInto<'a, PersonDeep<'s>> for Context<'s> {
+        type Handle = Interned<'a, PersonDeep<'s>>;
+
+        fn intern_value(&'a self, value: PersonDeep<'s>) -> Interned<'a, PersonDeep<'s>> {
+            self.people.intern(value)
+        }
+    }
+
+    intern_deep! {
+        impl<'a, 's: 'a> InternDeep<'a, Context<'s>> for Person => PersonDeep<'s> { name, age, tags, links }
+    }
+
+    fn person() -> Person {
+        let mut links = BTreeMap::new();
+        links.insert(1, Some(String::from("a")));
+        links.insert(2, None);
+        Person {
+            name: String::from("a"),
+            age: 3,
+            tags: vec![String::from("b"), String::from("a")],
+            links: links,
+        }
+    }
+
+    #[test]
+    fn intern_deep() {
+        let strings = Interner::new();
+        let cx = Context {
+            strings: &strings,
+            people: Interner::new(),
+        };
+        let deep = person().intern_deep(&cx);
+        assert_eq!(deep.name, deep.tags[1]);
+        assert_eq!(deep.links[&1], Some(deep.name.clone()));
+        assert_eq!(deep.age, 3);
+        assert_eq!(strings.len(), 2);
+
+        // The person is hash-consed too.
+        assert_eq!(person().intern_deep(&cx), deep);
+        assert_eq!(cx.people.len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+//! Syncing interners incrementally. One interner exports the values added to it since a mark,
+//! and others import them, so that a set of workers can converge on the same values without
+//! resending everything each time.
+
+use std::hash::Hash;
+
+use {BuildMap, InternError, InternKey, Interner, MapBackend};
+
+/// A point in an interner's history to export changes since. Marks count the values interned
+/// so far, and are only meaningful to the interner they came from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeltaMark(u64);
+
+/// The values added to an interner between two marks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delta<T, const N: usize = 20> {
+    /// Each new value, under its exported key in the exporting interner. See
+    /// `Interner::exported_key`.
+    pub entries: Vec<(InternKey<N>, T)>,
+    /// The mark to export the next delta since.
+    pub mark: DeltaMark,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// The current mark. Deltas exported since it will hold every value added after now.
+    /// Panics unless the interner was built with `InternerBuilder::stable_ids`.
+    pub fn delta_mark(&self) -> DeltaMark {
+        match self.stable_ids {
+            Some(ref ids) => DeltaMark(ids.lock().unwrap().len() as u64),
+            None => panic!("deltas need an interner built with stable IDs"),
+        }
+    }
+
+    /// Copy out every value first interned after `mark`, in the order they were interned.
+    /// `DeltaMark::default()` exports everything. Values which have since been removed are
+    /// left out, and since stable IDs are kept for good, a value that's removed and
+    /// re-interned isn't exported again. Panics unless the interner was built with
+    /// `InternerBuilder::stable_ids`.
+    pub fn export_since(&self, mark: DeltaMark) -> Delta<T, N>
+            where T: Clone
+    {
+        // Don't hold the ID table's lock while taking a shard's, since inserts take them the
+        // other way around.
+        let (keys, until) = match self.stable_ids {
+            Some(ref ids) => {
+                let ids = ids.lock().unwrap();
+                (ids.keys_from(mark.0 as usize), DeltaMark(ids.len() as u64))
+            },
+            None => panic!("deltas need an interner built with stable IDs"),
+        };
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            let map = self.read(&key).unwrap();
+            if let Some(field) = map.get(&key) {
+                entries.push((self.exported_key(&key), field.data.clone()));
+            }
+        }
+        Delta {
+            entries: entries,
+            mark: until,
+        }
+    }
+
+    /// Add the values in `delta`, returning how many were new. Like `import_jsonl`, values
+    /// are re-keyed for this interner and don't have any handles, so the delta's keys only
+    /// match this interner's if both key values the same way.
+    pub fn import_delta(&self, delta: Delta<T, N>) -> Result<usize, InternError>
+            where T: Hash
+    {
+        let mut imported = 0;
+        for (_, data) in delta.entries {
+            if try!(self.insert_unreferenced(data)) {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeltaMark;
+    use Interner;
+
+    #[test]
+    fn sync() {
+        let source = Interner::builder().stable_ids().build();
+        let worker: Interner<String> = Interner::new();
+        let a = source.intern(String::from("a"));
+        let b = source.intern(String::from("b"));
+
+        let delta = source.export_since(DeltaMark::default());
+        assert_eq!(delta.entries.len(), 2);
+        assert_eq!(delta.entries[0], (a.key().clone(), String::from("a")));
+        assert_eq!(worker.import_delta(delta.clone()).unwrap(), 2);
+        assert_eq!(worker.import_delta(delta.clone()).unwrap(), 0);
+
+        let c = source.intern(String::from("c"));
+        let _a = source.intern(String::from("a"));
+        drop(b);
+        source.reclaim().unwrap();
+        let next = source.export_since(delta.mark);
+        assert_eq!(next.entries, vec![(c.key().clone(), String::from("c"))]);
+        assert_eq!(next.mark, source.delta_mark());
+        assert_eq!(worker.import_delta(next).unwrap(), 1);
+        assert_eq!(worker.get("c").map(|c| c.key().clone()), Some(c.key().clone()));
+        assert!(source.export_since(source.delta_mark()).entries.is_empty());
+    }
+}
@@ -0,0 +1,448 @@
+//! The digests keys can be derived with.
+
+use std::cmp;
+use std::hash::{Hash, Hasher};
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crypto::digest::Digest;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use Salt;
+
+/// How an interner derives keys from values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum KeyDigest {
+    /// SHA-1, mixed with the interner's salt if it has one. Keys wider than 20 bytes use
+    /// SHA-256 instead. SHA-1 uses the CPU's SHA extensions on x86-64 when it has them.
+    #[default]
+    Sha1,
+    /// 128-bit SipHash-1-3, keyed with the interner's salt if it has one. Much faster than
+    /// SHA-1, but it isn't a cryptographic digest: without a secret salt, collisions can be
+    /// searched for deliberately. Any bytes of a key past the first 16 are derived from them.
+    Fast128,
+    /// 128-bit SipHash-2-4 keyed with the interner's salt, which is generated at random if
+    /// none is given. Slower than `Fast128` but still much faster than SHA-1, and keys can't
+    /// be predicted by anyone who doesn't know the salt. Like `Fast128`, any bytes of a key
+    /// past the first 16 are derived from them.
+    Sip128,
+    /// SHA-1 over the SHA-1 digests of each megabyte of a value, so that large values can
+    /// be hashed a chunk per core. Chunks are hashed in parallel with the `rayon` feature,
+    /// and one at a time without it, giving the same keys either way. Each value is copied
+    /// into a buffer to be split, so this only pays off for values of several megabytes.
+    Chunked,
+}
+
+/// Streaming SHA-1 which compresses blocks with the SHA extensions if the CPU has them.
+/// Values are hashed through `Hasher::write`, so it digests exactly the bytes a `Hash` impl
+/// writes.
+pub(crate) struct Sha1 {
+    state: [u32; 5],
+    block: [u8; 64],
+    nblock: usize,
+    length: u64,
+    accelerated: bool,
+}
+
+impl Sha1 {
+    pub(crate) fn new() -> Sha1 {
+        Sha1::with_acceleration(sha_extensions_detected())
+    }
+
+    fn with_acceleration(accelerated: bool) -> Sha1 {
+        Sha1 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            block: [0; 64],
+            nblock: 0,
+            length: 0,
+            accelerated: accelerated,
+        }
+    }
+
+    fn compress(&mut self) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if self.accelerated {
+                unsafe { compress_sha_ni(&mut self.state, &self.block) };
+                return;
+            }
+        }
+        compress_portable(&mut self.state, &self.block);
+    }
+
+    pub(crate) fn finish160(mut self) -> [u8; 20] {
+        let bits = self.length.wrapping_mul(8);
+        self.write(&[0x80]);
+        while self.nblock != 56 {
+            self.write(&[0]);
+        }
+        self.write(&bits.to_be_bytes());
+        let mut out = [0; 20];
+        for (chunk, word) in out.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+}
+
+impl Hasher for Sha1 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len() as u64);
+        while !bytes.is_empty() {
+            let n = cmp::min(64 - self.nblock, bytes.len());
+            self.block[self.nblock..self.nblock + n].copy_from_slice(&bytes[..n]);
+            self.nblock += n;
+            bytes = &bytes[n..];
+            if self.nblock == 64 {
+                self.compress();
+                self.nblock = 0;
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        ((self.state[0] as u64) << 32) | self.state[1] as u64
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn sha_extensions_detected() -> bool {
+    is_x86_feature_detected!("sha") && is_x86_feature_detected!("sse4.1")
+        && is_x86_feature_detected!("ssse3")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn sha_extensions_detected() -> bool {
+    false
+}
+
+fn compress_portable(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0u32; 80];
+    for (i, chunk) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let (mut a, mut b, mut c, mut d, mut e) = (state[0], state[1], state[2], state[3], state[4]);
+    for (i, &wi) in w.iter().enumerate() {
+        let (f, k) = match i / 20 {
+            0 => ((b & c) | (!b & d), 0x5a827999),
+            1 => (b ^ c ^ d, 0x6ed9eba1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(wi);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+// Four rounds with round function `f`, after adding the next four message words to `e`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sha,sse2")]
+unsafe fn rounds4(abcd: __m128i, e: __m128i, w: __m128i, f: usize) -> __m128i {
+    let e = _mm_sha1nexte_epu32(e, w);
+    match f {
+        0 => _mm_sha1rnds4_epu32(abcd, e, 0),
+        1 => _mm_sha1rnds4_epu32(abcd, e, 1),
+        2 => _mm_sha1rnds4_epu32(abcd, e, 2),
+        _ => _mm_sha1rnds4_epu32(abcd, e, 3),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+unsafe fn compress_sha_ni(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mask = _mm_set_epi64x(0x0001020304050607, 0x08090a0b0c0d0e0f);
+    let abcd = _mm_set_epi32(state[0] as i32, state[1] as i32, state[2] as i32, state[3] as i32);
+    let e = _mm_set_epi32(state[4] as i32, 0, 0, 0);
+    let ptr = block.as_ptr() as *const __m128i;
+    let mut w0 = _mm_shuffle_epi8(_mm_loadu_si128(ptr), mask);
+    let mut w1 = _mm_shuffle_epi8(_mm_loadu_si128(ptr.offset(1)), mask);
+    let mut w2 = _mm_shuffle_epi8(_mm_loadu_si128(ptr.offset(2)), mask);
+    let mut w3 = _mm_shuffle_epi8(_mm_loadu_si128(ptr.offset(3)), mask);
+
+    // Each group of four rounds writes one of the two state registers, which hold `abcd`
+    // and `e` by turns. Past the first sixteen rounds, the message words are scheduled from
+    // the previous sixteen.
+    let mut h0 = abcd;
+    let mut h1 = _mm_sha1rnds4_epu32(h0, _mm_add_epi32(e, w0), 0);
+    h0 = rounds4(h1, h0, w1, 0);
+    h1 = rounds4(h0, h1, w2, 0);
+    h0 = rounds4(h1, h0, w3, 0);
+    for group in 4..20 {
+        let w = _mm_sha1msg2_epu32(_mm_xor_si128(_mm_sha1msg1_epu32(w0, w1), w2), w3);
+        w0 = w1;
+        w1 = w2;
+        w2 = w3;
+        w3 = w;
+        if group % 2 == 0 {
+            h1 = rounds4(h0, h1, w, group / 5);
+        } else {
+            h0 = rounds4(h1, h0, w, group / 5);
+        }
+    }
+
+    let abcd = _mm_add_epi32(abcd, h0);
+    let e = _mm_sha1nexte_epu32(h1, e);
+    state[0] = _mm_extract_epi32(abcd, 3) as u32;
+    state[1] = _mm_extract_epi32(abcd, 2) as u32;
+    state[2] = _mm_extract_epi32(abcd, 1) as u32;
+    state[3] = _mm_extract_epi32(abcd, 0) as u32;
+    state[4] = _mm_extract_epi32(e, 3) as u32;
+}
+
+/// The size of the chunks `KeyDigest::Chunked` splits values into.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Collects the bytes a value's `Hash` impl writes.
+struct Collect(Vec<u8>);
+
+impl Hasher for Collect {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        0
+    }
+}
+
+fn chunk_digest(chunk: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.write(chunk);
+    hasher.finish160()
+}
+
+/// The SHA-1 digest of each `CHUNK_SIZE` chunk of the bytes `data` hashes as.
+pub(crate) fn chunk_digests<T: ?Sized + Hash>(data: &T) -> Vec<[u8; 20]> {
+    let mut bytes = Collect(Vec::new());
+    data.hash(&mut bytes);
+    hash_chunks(&bytes.0)
+}
+
+#[cfg(feature = "rayon")]
+fn hash_chunks(bytes: &[u8]) -> Vec<[u8; 20]> {
+    bytes.par_chunks(CHUNK_SIZE).map(chunk_digest).collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn hash_chunks(bytes: &[u8]) -> Vec<[u8; 20]> {
+    bytes.chunks(CHUNK_SIZE).map(chunk_digest).collect()
+}
+
+/// Streaming SipHash with a 128-bit output, generic over its round counts.
+#[derive(Clone)]
+pub(crate) struct Sip128 {
+    v: [u64; 4],
+    tail: u64,
+    ntail: usize,
+    length: usize,
+    c_rounds: usize,
+    d_rounds: usize,
+}
+
+impl Sip128 {
+    pub(crate) fn new(key: [u64; 2], c_rounds: usize, d_rounds: usize) -> Sip128 {
+        Sip128 {
+            v: [key[0] ^ 0x736f6d6570736575,
+                key[1] ^ 0x646f72616e646f6d ^ 0xee,
+                key[0] ^ 0x6c7967656e657261,
+                key[1] ^ 0x7465646279746573],
+            tail: 0,
+            ntail: 0,
+            length: 0,
+            c_rounds: c_rounds,
+            d_rounds: d_rounds,
+        }
+    }
+
+    /// Key with `salt`'s 16 bytes, or with zeroes.
+    pub(crate) fn keyed(salt: Option<&Salt>, c_rounds: usize, d_rounds: usize) -> Sip128 {
+        let mut key = [0u64; 2];
+        if let Some(salt) = salt {
+            for (i, &b) in salt.data.iter().enumerate() {
+                key[i / 8] |= (b as u64) << (8 * (i % 8));
+            }
+        }
+        Sip128::new(key, c_rounds, d_rounds)
+    }
+
+    fn round(&mut self) {
+        let v = &mut self.v;
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(&mut self, m: u64) {
+        self.v[3] ^= m;
+        for _ in 0..self.c_rounds {
+            self.round();
+        }
+        self.v[0] ^= m;
+    }
+
+    pub(crate) fn finish128(mut self) -> [u8; 16] {
+        let b = ((self.length as u64 & 0xff) << 56) | self.tail;
+        self.compress(b);
+        self.v[2] ^= 0xee;
+        for _ in 0..self.d_rounds {
+            self.round();
+        }
+        let h1 = self.v[0] ^ self.v[1] ^ self.v[2] ^ self.v[3];
+        self.v[1] ^= 0xdd;
+        for _ in 0..self.d_rounds {
+            self.round();
+        }
+        let h2 = self.v[0] ^ self.v[1] ^ self.v[2] ^ self.v[3];
+        let mut out = [0u8; 16];
+        for i in 0..8 {
+            out[i] = (h1 >> (8 * i)) as u8;
+            out[8 + i] = (h2 >> (8 * i)) as u8;
+        }
+        out
+    }
+}
+
+impl Hasher for Sip128 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.length += bytes.len();
+        for &b in bytes {
+            self.tail |= (b as u64) << (8 * self.ntail);
+            self.ntail += 1;
+            if self.ntail == 8 {
+                let m = self.tail;
+                self.compress(m);
+                self.tail = 0;
+                self.ntail = 0;
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let out = self.clone().finish128();
+        out[..8].iter().rev().fold(0, |h, &b| (h << 8) | b as u64)
+    }
+}
+
+/// Feeds the bytes a `Hash` impl writes into a rust-crypto `Digest`.
+pub(crate) struct DigestWriter<'a, D: 'a>(pub(crate) &'a mut D);
+
+impl<'a, D: Digest> Hasher for DigestWriter<'a, D> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.input(bytes)
+    }
+
+    fn finish(&self) -> u64 {
+        unreachable!("DigestWriter is only written to")
+    }
+}
+
+/// Hash `data` with 128-bit SipHash using the given round counts.
+pub(crate) fn sip128<T: ?Sized + Hash>(salt: Option<&Salt>, data: &T, c_rounds: usize, d_rounds: usize) -> [u8; 16] {
+    let mut hasher = Sip128::keyed(salt, c_rounds, d_rounds);
+    data.hash(&mut hasher);
+    hasher.finish128()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use crypto::digest::Digest;
+    use crypto::{sha1, sha2};
+
+    use super::{Sha1, Sip128};
+    use {InternKey, Interner, KeyDigest, Salt};
+
+    #[test]
+    fn fast128_keys() {
+        let fast = Interner::builder().key_digest(KeyDigest::Fast128).build();
+        let sha = Interner::new();
+        let a = fast.intern(String::from("a"));
+        assert_eq!(fast.intern_borrowed("a"), a);
+        assert!(a.key() != sha.intern(String::from("a")).key());
+        assert!(a.key() != fast.intern(String::from("b")).key());
+
+        let salted = Interner::builder().key_digest(KeyDigest::Fast128).salt(Salt::new([1; 16])).build();
+        assert!(a.key() != salted.intern(String::from("a")).key());
+    }
+
+    #[test]
+    fn sip128_keys() {
+        // The reference SipHash-2-4-128 test vector for the empty message.
+        let hasher = Sip128::new([0x0706050403020100, 0x0f0e0d0c0b0a0908], 2, 4);
+        assert_eq!(hasher.finish128(), [0xa3, 0x81, 0x7f, 0x04, 0xba, 0x25, 0xa8, 0xe6,
+                                        0x6d, 0xf6, 0x72, 0x14, 0xc7, 0x55, 0x02, 0x93]);
+
+        let a = Interner::builder().key_digest(KeyDigest::Sip128).build();
+        let b = Interner::builder().key_digest(KeyDigest::Sip128).build();
+        let ka = a.intern(String::from("a"));
+        assert_eq!(a.intern_borrowed("a"), ka);
+        assert!(ka.key() != b.intern(String::from("a")).key());
+    }
+
+    #[test]
+    fn sha1_matches_reference() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 7 + 3) as u8).collect();
+        for &len in &[0, 1, 55, 56, 63, 64, 65, 128, 300] {
+            let mut reference = sha1::Sha1::new();
+            reference.input(&data[..len]);
+            let mut expected = [0; 20];
+            reference.result(&mut expected);
+            for &accelerated in &[false, super::sha_extensions_detected()] {
+                let mut hasher = Sha1::with_acceleration(accelerated);
+                // Split the input to exercise buffering across writes.
+                hasher.write(&data[..len / 3]);
+                hasher.write(&data[len / 3..len]);
+                assert_eq!(hasher.finish160(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_sha1_keys() {
+        // Keys wider than SHA-1 are SHA-256 digests of what the value hashes as.
+        let interner = Interner::builder_with_key_bytes::<32>().build();
+        let a = interner.intern(String::from("a"));
+        assert_eq!(interner.intern_borrowed("a"), a);
+        let mut reference = sha2::Sha256::new();
+        reference.input(b"a\xff");
+        let mut expected = [0; 32];
+        reference.result(&mut expected);
+        assert_eq!(*a.key(), InternKey::from_bytes(expected));
+
+        let salted = Interner::builder_with_key_bytes::<32>().salt(Salt::new([1; 16])).build();
+        assert!(salted.intern(String::from("a")).key() != a.key());
+    }
+
+    #[test]
+    fn chunked_keys() {
+        let chunked = Interner::builder().key_digest(KeyDigest::Chunked).build();
+        let big = vec![7u8; 3 * super::CHUNK_SIZE + 5];
+        let a = chunked.intern(big.clone());
+        assert_eq!(chunked.intern(big.clone()), a);
+        assert_eq!(super::chunk_digests(&big).len(), 4);
+        let mut other = big.clone();
+        other[2 * super::CHUNK_SIZE] = 0;
+        assert!(chunked.intern(other).key() != a.key());
+        assert!(Interner::new().intern(big).key() != a.key());
+    }
+}
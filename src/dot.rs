@@ -0,0 +1,76 @@
+//! Graphviz export of DAGs of interned values, to show which subtrees are shared.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use {BuildMap, InternKey, Interned};
+
+/// Write the graph of values reachable from `roots` to `out` in DOT format. `children` gives
+/// the handles a value points to. Each distinct interned value appears as exactly one node,
+/// labelled with a prefix of its key and its `Debug` representation.
+pub fn write_dot<'a, 'r, T, S, I, F, W, const N: usize>(roots: I, children: F, out: &mut W) -> fmt::Result
+        where T: fmt::Debug + 'a,
+              S: BuildMap<T, N> + 'a,
+              'a: 'r,
+              I: IntoIterator<Item=&'r Interned<'a, T, S, N>>,
+              F: for<'b> Fn(&'b T) -> Vec<&'b Interned<'a, T, S, N>>,
+              W: fmt::Write
+{
+    fn node_id<const N: usize>(key: &InternKey<N>) -> String {
+        format!("n{:08x}{:08x}", key.word(0), key.word(1))
+    }
+
+    try!(writeln!(out, "digraph interned {{"));
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&Interned<'a, T, S, N>> = roots.into_iter().collect();
+    while let Some(handle) = stack.pop() {
+        let key = &handle.field.key;
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let label = format!("{:08x}: {:?}", key.word(0), &**handle);
+        try!(writeln!(out, "    {} [label=\"{}\"];", node_id(key), escape(&label)));
+        for child in children(&handle.field.data) {
+            try!(writeln!(out, "    {} -> {};", node_id(key), node_id(&child.field.key)));
+            stack.push(child);
+        }
+    }
+    writeln!(out, "}}")
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_dot;
+    use {Interned, Interner};
+
+    #[derive(Hash, Debug)]
+    enum Expr<'i> {
+        Var(&'static str),
+        Add(Interned<'i, Expr<'i>>, Interned<'i, Expr<'i>>),
+    }
+
+    fn children<'b, 'i>(expr: &'b Expr<'i>) -> Vec<&'b Interned<'i, Expr<'i>>> {
+        match *expr {
+            Expr::Var(..) => Vec::new(),
+            Expr::Add(ref l, ref r) => vec![l, r],
+        }
+    }
+
+    #[test]
+    fn shared_subtree() {
+        let interner: &'static Interner<Expr<'static>> = Box::leak(Box::new(Interner::new()));
+        let x = interner.intern(Expr::Var("x"));
+        let sum = interner.intern(Expr::Add(x.clone(), x.clone()));
+        let root = interner.intern(Expr::Add(sum.clone(), x.clone()));
+        let mut out = String::new();
+        write_dot(vec![&root], children, &mut out).unwrap();
+        assert!(out.starts_with("digraph interned {\n"));
+        assert_eq!(out.matches("[label=").count(), 3);
+        assert_eq!(out.matches(" -> ").count(), 4);
+        assert!(out.contains("Var(\\\"x\\\")"));
+    }
+}
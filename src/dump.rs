@@ -0,0 +1,57 @@
+//! Human-readable dumps of an interner's table, for debugging.
+
+use std::fmt;
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildMap, Interner, MapBackend};
+
+impl<T: fmt::Debug, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Write out every entry, one per line, as its key, its handle count and its value.
+    /// Entries are sorted by key so dumps can be diffed.
+    pub fn dump<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        let mut lines = Vec::new();
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            for field in map.iter() {
+                let key = self.exported_key(&field.key);
+                let line = format!("{} count={} {:?}", key, field.count.load(Relaxed), field.data);
+                lines.push((key, line));
+            }
+        }
+        lines.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, line) in lines {
+            try!(writeln!(out, "{}", line));
+        }
+        Ok(())
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> fmt::Debug for Interner<T, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interner")
+         .field("entries", &self.len())
+         .field("shards", &self.shards.len())
+         .field("salted", &self.salt.is_some())
+         .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn dump() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let _a2 = a.clone();
+        let _b = interner.intern(String::from("b"));
+        let mut out = String::new();
+        interner.dump(&mut out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().any(|line| line.ends_with(" count=2 \"a\"")));
+        assert!(lines.iter().any(|line| line.ends_with(" count=1 \"b\"")));
+        assert!(format!("{:?}", interner).starts_with("Interner { entries: 2,"));
+    }
+}
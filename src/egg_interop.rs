@@ -0,0 +1,121 @@
+//! Conversion between hash-consed trees and `egg` expressions. Shared subtrees stay shared:
+//! each distinct node appears in a `RecExpr` once, however many parents it has.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use egg::{Id, Language, RecExpr};
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner};
+
+/// A node type that corresponds to an `egg` language.
+pub trait EggNode<'a, S: BuildMap<Self, N> = BuildKeyHasher, const N: usize = 20>: Hash + Sized + 'a {
+    type Language: Language;
+
+    /// The children of this node, in the order `to_egg` expects their ids.
+    fn children(&self) -> Vec<&Interned<'a, Self, S, N>>;
+
+    /// Build the `egg` node given the ids of this node's children.
+    fn to_egg(&self, children: &[Id]) -> Self::Language;
+
+    /// Build a node from an `egg` node, given handles to the node's children in the order
+    /// of `Language::children`.
+    fn from_egg(node: &Self::Language, children: Vec<Interned<'a, Self, S, N>>) -> Self;
+}
+
+/// Convert the tree under `root` to an `egg` expression.
+pub fn to_rec_expr<'a, T, S, const N: usize>(root: &Interned<'a, T, S, N>) -> RecExpr<T::Language>
+        where T: EggNode<'a, S, N>,
+              S: BuildMap<T, N>
+{
+    fn add<'a, T, S, const N: usize>(node: &Interned<'a, T, S, N>,
+                     ids: &mut HashMap<InternKey<N>, Id, BuildKeyHasher>,
+                     expr: &mut RecExpr<T::Language>) -> Id
+            where T: EggNode<'a, S, N>,
+                  S: BuildMap<T, N>
+    {
+        if let Some(&id) = ids.get(node.key()) {
+            return id;
+        }
+        let children: Vec<Id> = node.children().into_iter().map(|child| add(child, ids, expr)).collect();
+        let id = expr.add(node.to_egg(&children));
+        ids.insert(node.key().clone(), id);
+        id
+    }
+
+    let mut expr = RecExpr::default();
+    add(root, &mut HashMap::default(), &mut expr);
+    expr
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Intern every node of an `egg` expression, returning a handle to its root (the last
+    /// node). Panics if the expression is empty.
+    pub fn intern_rec_expr<'a>(&'a self, expr: &RecExpr<T::Language>) -> Interned<'a, T, S, N>
+            where T: EggNode<'a, S, N>
+    {
+        let mut handles: Vec<Interned<'a, T, S, N>> = Vec::with_capacity(expr.as_ref().len());
+        for node in expr.as_ref() {
+            let children = node.children().iter().map(|&id| handles[usize::from(id)].clone()).collect();
+            let handle = self.intern(T::from_egg(node, children));
+            handles.push(handle);
+        }
+        handles.pop().expect("empty RecExpr")
+    }
+}
+
+#[cfg(all(test, feature = "egg"))]
+mod tests {
+    use egg::{Id, SymbolLang};
+
+    use super::{to_rec_expr, EggNode};
+    use {Interned, Interner};
+
+    #[derive(Hash, Debug)]
+    enum Expr<'i> {
+        Num(u32),
+        Add(Interned<'i, Expr<'i>>, Interned<'i, Expr<'i>>),
+    }
+
+    impl<'i> EggNode<'i> for Expr<'i> {
+        type Language = SymbolLang;
+
+        fn children(&self) -> Vec<&Interned<'i, Expr<'i>>> {
+            match *self {
+                Expr::Num(_) => vec![],
+                Expr::Add(ref a, ref b) => vec![a, b],
+            }
+        }
+
+        fn to_egg(&self, children: &[Id]) -> SymbolLang {
+            match *self {
+                Expr::Num(n) => SymbolLang::leaf(n.to_string()),
+                Expr::Add(..) => SymbolLang::new("+", children.to_vec()),
+            }
+        }
+
+        fn from_egg(node: &SymbolLang, mut children: Vec<Interned<'i, Expr<'i>>>) -> Expr<'i> {
+            match children.pop() {
+                Some(b) => Expr::Add(children.pop().unwrap(), b),
+                None => Expr::Num(node.op.as_str().parse().unwrap()),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let interner: &'static Interner<Expr<'static>> = Box::leak(Box::new(Interner::new()));
+        let one = interner.intern(Expr::Num(1));
+        let two = interner.intern(Expr::Add(one.clone(), one.clone()));
+        let root = interner.intern(Expr::Add(two, one));
+        let expr = to_rec_expr(&root);
+        // `1` is under both additions, but appears once.
+        assert_eq!(expr.as_ref().len(), 3);
+        assert_eq!(expr.as_ref().iter().filter(|node| node.op.as_str() == "1").count(), 1);
+
+        let back = interner.intern_rec_expr(&expr);
+        assert_eq!(back.key(), root.key());
+        assert_eq!(back, root);
+        assert_eq!(interner.len(), 3);
+    }
+}
@@ -0,0 +1,200 @@
+//! Looking up a value and deciding what to do about it under a single lock acquisition.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::sync::RwLockWriteGuard;
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildKeyHasher, BuildMap, InternField, InternKey, Interned, Interner, MapBackend};
+
+/// The entry for a value in an interner, from `Interner::entry`. It holds its shard's lock
+/// exclusively until it's dropped or consumed, so nothing can be inserted under its key or
+/// removed from it in the meantime.
+pub enum Entry<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    Occupied(OccupiedEntry<'a, T, S, N>),
+    Vacant(VacantEntry<'a, T, S, N>),
+}
+
+/// An entry for a value that's interned, although it may have no handles left.
+pub struct OccupiedEntry<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    map: RwLockWriteGuard<'a, S::Map>,
+    key: InternKey<N>,
+}
+
+/// An entry for a value that isn't interned.
+pub struct VacantEntry<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    map: RwLockWriteGuard<'a, S::Map>,
+    key: InternKey<N>,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// The entry for the value equal to `data`. This takes the lock on `data`'s shard until
+    /// the entry is done with, so interning into the same interner before then may deadlock.
+    pub fn entry<'a, B: ?Sized + Hash>(&'a self, data: &B) -> Entry<'a, T, S, N>
+            where T: Borrow<B> + 'a
+    {
+        // The value isn't known yet, so only make room if there's none at all.
+        self.make_room(0);
+        let key = self.key_of(data);
+        let map = match self.write(&key) {
+            Ok(map) => map,
+            Err(e) => panic!("{}", e),
+        };
+        if map.get(&key).is_some() {
+            Entry::Occupied(OccupiedEntry {
+                interner: self,
+                map: map,
+                key: key,
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                interner: self,
+                map: map,
+                key: key,
+            })
+        }
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> Entry<'a, T, S, N> {
+    pub fn key(&self) -> &InternKey<N> {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+
+    /// The number of handles to the value, which is zero if it isn't interned.
+    pub fn count(&self) -> usize {
+        match *self {
+            Entry::Occupied(ref entry) => entry.count(),
+            Entry::Vacant(_) => 0,
+        }
+    }
+
+    /// A handle to the value, inserting `data` if it isn't interned yet.
+    pub fn or_insert(self, data: T) -> Interned<'a, T, S, N>
+            where T: Hash
+    {
+        self.or_insert_with(|| data)
+    }
+
+    /// A handle to the value, inserting the value `f` builds if it isn't interned yet. `f`
+    /// runs with the shard's lock held, so it mustn't intern into the same interner.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, f: F) -> Interned<'a, T, S, N>
+            where T: Hash
+    {
+        match self {
+            Entry::Occupied(entry) => {
+                entry.interner.note_intern();
+                entry.into_handle()
+            },
+            Entry::Vacant(entry) => {
+                entry.interner.note_intern();
+                entry.insert(f())
+            },
+        }
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> OccupiedEntry<'a, T, S, N> {
+    fn field(&self) -> &InternField<T, N> {
+        self.map.get(&self.key).unwrap()
+    }
+
+    pub fn key(&self) -> &InternKey<N> {
+        &self.key
+    }
+
+    pub fn get(&self) -> &T {
+        &self.field().data
+    }
+
+    /// The number of handles to the value. The entry's lock doesn't stop handles from being
+    /// cloned or dropped, so this can be out of date as soon as it's returned.
+    pub fn count(&self) -> usize {
+        self.field().count.load(Acquire)
+    }
+
+    /// Release the lock, keeping a handle to the value.
+    pub fn into_handle(self) -> Interned<'a, T, S, N> {
+        self.interner.handle(self.map.get(&self.key).unwrap())
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> VacantEntry<'a, T, S, N> {
+    pub fn key(&self) -> &InternKey<N> {
+        &self.key
+    }
+
+    /// Intern `data` under the entry's key and release the lock. `data` must be equal to the
+    /// value the entry was looked up with, which is checked in debug builds. Panics if there's
+    /// no room for it under the interner's limits.
+    pub fn insert(mut self, data: T) -> Interned<'a, T, S, N>
+            where T: Hash
+    {
+        if cfg!(debug_assertions) && self.interner.key_of(&data) != self.key {
+            // Don't poison the lock.
+            drop(self.map);
+            panic!("VacantEntry::insert given a value other than the one looked up");
+        }
+        let field = Box::new(InternField::new(self.key, data));
+        if let Err(e) = self.interner.admit(&field) {
+            // Don't poison the lock.
+            drop(self.map);
+            panic!("{}", e);
+        }
+        let (field, _) = self.map.get_or_insert(field);
+        self.interner.inserted(field);
+        self.interner.new_handle(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+    use Interner;
+
+    #[test]
+    fn entries() {
+        let interner = Interner::new();
+        let a = match interner.entry("a") {
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &interner.key_of("a"));
+                entry.insert(String::from("a"))
+            },
+            Entry::Occupied(_) => panic!("\"a\" isn't interned yet"),
+        };
+        assert_eq!(a, interner.intern_borrowed("a"));
+
+        match interner.entry("a") {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.get(), "a");
+                assert_eq!(entry.count(), 1);
+            },
+            Entry::Vacant(_) => panic!("\"a\" is interned"),
+        }
+        let entry = interner.entry("a");
+        assert_eq!(entry.count(), 1);
+        assert_eq!(entry.or_insert_with(|| panic!("\"a\" is interned")), a);
+
+        let b = interner.entry("b");
+        assert_eq!(b.count(), 0);
+        let b = b.or_insert(String::from("b"));
+        assert_eq!(&b[..], "b");
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "other than the one looked up")]
+    fn insert_other_value() {
+        let interner = Interner::new();
+        let entry = interner.entry("a");
+        if let Entry::Vacant(entry) = entry {
+            entry.insert(String::from("b"));
+        }
+    }
+}
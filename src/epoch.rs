@@ -0,0 +1,78 @@
+//! Epochs, for telling apart the entries used in different sessions (eg. successive
+//! compilations) that share one interner.
+
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildMap, EvictionReason, InternError, InternField, Interned, Interner};
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Stamp `field` with the current epoch.
+    pub(crate) fn touch(&self, field: &InternField<T, N>) {
+        let epoch = self.epoch.load(Relaxed);
+        // Most lookups are of entries already stamped, so avoid writing to the entry.
+        if field.epoch.load(Relaxed) != epoch {
+            field.epoch.store(epoch, Relaxed);
+        }
+    }
+
+    /// The current epoch. Starts at 0.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Relaxed)
+    }
+
+    /// Start a new epoch, returning its number. Entries are stamped with the epoch whenever
+    /// they're inserted or looked up, so afterwards `Interned::is_current` is false for
+    /// handles to anything that hasn't been interned again since.
+    pub fn advance_epoch(&self) -> u64 {
+        self.epoch.fetch_add(1, Relaxed) + 1
+    }
+
+    /// Remove every unused entry that hasn't been touched in the current epoch, including
+    /// ones which were never retired, such as entries moved in by `drain_into`. Returns the
+    /// number removed. Entries which still have handles are left alone. The removed entries
+    /// are passed to the `on_evict` callback, if there is one.
+    pub fn sweep_stale(&self) -> Result<usize, InternError> {
+        let epoch = self.epoch();
+        let removed = try!(self.sweep(|field| field.epoch.load(Relaxed) != epoch));
+        let count = removed.len();
+        self.evicted(removed, EvictionReason::Stale);
+        Ok(count)
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// The epoch in which this entry was last inserted or looked up.
+    pub fn epoch(&self) -> u64 {
+        self.field.epoch.load(Relaxed)
+    }
+
+    /// Whether this entry has been inserted or looked up in the interner's current epoch.
+    pub fn is_current(&self) -> bool {
+        self.epoch() == self.interner.epoch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn epochs() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        assert!(a.is_current());
+        assert_eq!(interner.advance_epoch(), 1);
+        assert!(!a.is_current());
+        assert!(interner.intern_borrowed("a").is_current());
+        assert!(a.is_current());
+        assert_eq!(b.epoch(), 0);
+
+        let other = Interner::new();
+        drop(b);
+        interner.drain_into(&other).unwrap();
+        other.advance_epoch();
+        assert_eq!(other.sweep_stale().unwrap(), 1);
+        assert!(other.is_empty());
+    }
+}
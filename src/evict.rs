@@ -0,0 +1,225 @@
+//! Eviction: removing entries which have no handles for some reason other than their last
+//! handle having been dropped, and telling the application about it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildKeyHasher, BuildMap, InternField, InternKey, Interner, MapBackend};
+
+/// Why an entry was evicted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EvictionReason {
+    /// It hadn't been used in the current epoch when `Interner::sweep_stale` was called.
+    Stale,
+    /// It was removed to make room for a new entry under `max_entries` or `max_bytes`.
+    Capacity,
+    /// It was removed by `Interner::trim` to shed memory.
+    Pressure,
+}
+
+/// Chooses which entries to evict when an interner needs room, given a record of how
+/// entries are used. An interner with a policy evicts its victims one at a time until
+/// there's room, where one without evicts every entry without handles.
+///
+/// The interner calls these methods with one of its shard locks held, so they mustn't use
+/// the interner.
+pub trait EvictionPolicy<const N: usize = 20> {
+    /// An entry was inserted.
+    fn on_insert(&mut self, key: &InternKey<N>);
+
+    /// A handle was taken to an entry, including straight after it was inserted.
+    fn on_hit(&mut self, key: &InternKey<N>);
+
+    /// An entry was removed, whether it was evicted or not.
+    fn on_remove(&mut self, key: &InternKey<N>);
+
+    /// The entry to evict next, or `None` to give up. A victim which turns out to still have
+    /// handles is kept, and counted as a hit.
+    fn pick_victim(&mut self) -> Option<InternKey<N>>;
+}
+
+/// Evict the least recently used entry first.
+pub struct Lru<const N: usize = 20> {
+    clock: u64,
+    last_used: HashMap<InternKey<N>, u64, BuildKeyHasher>,
+    by_last_used: BTreeMap<u64, InternKey<N>>,
+}
+
+impl<const N: usize> Lru<N> {
+    pub fn new() -> Lru<N> {
+        Lru {
+            clock: 0,
+            last_used: HashMap::default(),
+            by_last_used: BTreeMap::new(),
+        }
+    }
+}
+
+impl<const N: usize> Default for Lru<N> {
+    fn default() -> Lru<N> {
+        Lru::new()
+    }
+}
+
+impl<const N: usize> EvictionPolicy<N> for Lru<N> {
+    fn on_insert(&mut self, key: &InternKey<N>) {
+        self.on_hit(key);
+    }
+
+    fn on_hit(&mut self, key: &InternKey<N>) {
+        self.clock += 1;
+        if let Some(last_used) = self.last_used.insert(key.clone(), self.clock) {
+            self.by_last_used.remove(&last_used);
+        }
+        self.by_last_used.insert(self.clock, key.clone());
+    }
+
+    fn on_remove(&mut self, key: &InternKey<N>) {
+        if let Some(last_used) = self.last_used.remove(key) {
+            self.by_last_used.remove(&last_used);
+        }
+    }
+
+    fn pick_victim(&mut self) -> Option<InternKey<N>> {
+        self.by_last_used.values().next().cloned()
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    pub(crate) fn policy_insert(&self, key: &InternKey<N>) {
+        if let Some(ref policy) = self.eviction_policy {
+            policy.lock().unwrap().on_insert(key);
+        }
+    }
+
+    pub(crate) fn policy_hit(&self, key: &InternKey<N>) {
+        if let Some(ref policy) = self.eviction_policy {
+            policy.lock().unwrap().on_hit(key);
+        }
+    }
+
+    pub(crate) fn policy_remove(&self, key: &InternKey<N>) {
+        if let Some(ref policy) = self.eviction_policy {
+            policy.lock().unwrap().on_remove(key);
+        }
+    }
+
+    /// Hand evicted entries to the `on_evict` callback, or just drop them. Called without
+    /// any locks held.
+    pub(crate) fn evicted(&self, fields: Vec<Box<InternField<T, N>>>, reason: EvictionReason) {
+        self.note_evicted(fields.len());
+        match self.on_evict {
+            Some(ref on_evict) => {
+                for field in fields {
+                    on_evict(field.data, reason);
+                }
+            },
+            None => drop(fields),
+        }
+    }
+
+    /// Try to make room for a new entry of `bytes` bytes: first by reclaiming entries whose
+    /// handles have all been dropped, and then by evicting other entries without handles.
+    pub(crate) fn make_room(&self, bytes: usize) {
+        if self.has_room(bytes) {
+            return;
+        }
+        drop(self.reclaim_retired());
+        if self.has_room(bytes) {
+            return;
+        }
+        let policy = match self.eviction_policy {
+            Some(ref policy) => policy,
+            None => {
+                if let Ok(removed) = self.sweep(|_| true) {
+                    self.evicted(removed, EvictionReason::Capacity);
+                }
+                return;
+            },
+        };
+
+        let mut evicted = Vec::new();
+        // Give up after as many victims as there are entries, in case the policy keeps
+        // picking entries with handles.
+        for _ in 0..self.len() {
+            if self.has_room(bytes) {
+                break;
+            }
+            let key = match policy.lock().unwrap().pick_victim() {
+                Some(key) => key,
+                None => break,
+            };
+            let mut map = match self.write(&key) {
+                Ok(map) => map,
+                Err(_) => break,
+            };
+            match map.get(&key).map(|field| 0 == field.count.load(Acquire)) {
+                Some(true) => {
+                    if let Some(field) = map.remove(&key) {
+                        self.removed(&field);
+                        evicted.push(field);
+                    }
+                },
+                Some(false) => self.policy_hit(&key),
+                None => self.policy_remove(&key),
+            }
+        }
+        self.evicted(evicted, EvictionReason::Capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{EvictionReason, Lru};
+    use Interner;
+
+    #[test]
+    fn on_evict() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = evicted.clone();
+        let interner = Interner::builder()
+                                .mark_and_sweep()
+                                .max_entries(2)
+                                .on_evict(move |s: String, reason| log.lock().unwrap().push((s, reason)))
+                                .build();
+        drop(interner.intern(String::from("a")));
+        interner.advance_epoch();
+        let b = interner.intern(String::from("b"));
+        assert_eq!(interner.sweep_stale().unwrap(), 1);
+        drop(interner.intern(String::from("c")));
+        let d = interner.intern(String::from("d"));
+        assert_eq!(*evicted.lock().unwrap(),
+                   [(String::from("a"), EvictionReason::Stale), (String::from("c"), EvictionReason::Capacity)]);
+
+        // Entries whose handles are dropped in the usual way aren't evictions.
+        let interner = Interner::builder().on_evict(|_: String, _| panic!("nothing was evicted")).build();
+        drop(interner.intern(String::from("a")));
+        interner.reclaim().unwrap();
+        assert!(interner.is_empty());
+        drop((b, d));
+    }
+
+    #[test]
+    fn lru() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = evicted.clone();
+        let interner = Interner::builder()
+                                .mark_and_sweep()
+                                .max_entries(3)
+                                .eviction_policy(Lru::new())
+                                .on_evict(move |s: String, _| log.lock().unwrap().push(s))
+                                .build();
+        let a = interner.intern(String::from("a"));
+        drop(interner.intern(String::from("b")));
+        drop(interner.intern(String::from("c")));
+        drop(interner.intern(String::from("b")));
+        // "a" is the least recently used, but it's still in use.
+        drop(interner.intern(String::from("d")));
+        drop(interner.intern(String::from("e")));
+        assert_eq!(*evicted.lock().unwrap(), ["c", "b"]);
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.get("a"), Some(a));
+    }
+}
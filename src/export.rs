@@ -0,0 +1,164 @@
+//! A deterministic binary export of an interner's values, for build artifacts which have to
+//! be reproducible. Entries are written in key order, and nothing that depends on how the
+//! interner was used (handle counts, epochs, insertion order) is written at all, so the same
+//! set of values always exports to the same bytes.
+//!
+//! Each entry is its exported key's bytes, then the length of its encoded value as a little-endian
+//! `u64`, then the encoded value.
+
+use std::hash::Hash;
+use std::io::{self, Read};
+
+use {BuildMap, InternKey, Interned, Interner, KeyDigest, MapBackend};
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// The form of `key` to show outside the interner. If the interner has an export salt,
+    /// this is a digest of the key keyed with the salt, so that it can't be matched against
+    /// guessed values' hashes. Otherwise it's the key itself.
+    pub fn exported_key(&self, key: &InternKey<N>) -> InternKey<N> {
+        match self.export_salt {
+            Some(ref salt) => InternKey::hash(KeyDigest::Sha1, Some(salt), key),
+            None => key.clone(),
+        }
+    }
+
+    /// Write every value to `out`, encoded by `encode`, sorted by exported key bytes. Returns
+    /// the number of entries written. Exports are only reproducible if `encode` is, and if
+    /// the interner's keys are (ie. its salts are fixed, if it has any). Entries whose
+    /// handles have all been dropped are reclaimed first rather than exported, so that the
+    /// export doesn't depend on when that happened.
+    pub fn export_sorted<W, F>(&self, mut out: W, mut encode: F) -> io::Result<usize>
+            where W: io::Write,
+                  F: FnMut(&T, &mut Vec<u8>)
+    {
+        drop(try!(self.reclaim_retired().map_err(io::Error::other)));
+        let mut entries: Vec<(InternKey<N>, Vec<u8>)> = Vec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            for field in map.iter() {
+                let mut bytes = Vec::new();
+                encode(&field.data, &mut bytes);
+                entries.push((self.exported_key(&field.key), bytes));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (key, bytes) in &entries {
+            try!(out.write_all(&key.to_bytes()));
+            try!(out.write_all(&(bytes.len() as u64).to_le_bytes()));
+            try!(out.write_all(bytes));
+        }
+        Ok(entries.len())
+    }
+
+    /// Read back entries written by `export_sorted`, decoding values with `decode`, and return
+    /// how many were new. Values are re-keyed for this interner and don't have any handles.
+    pub fn import_sorted<R, F>(&self, mut input: R, mut decode: F) -> io::Result<usize>
+            where R: io::Read,
+                  F: FnMut(&[u8]) -> io::Result<T>,
+                  T: Hash
+    {
+        let mut imported = 0;
+        let mut key = [0; N];
+        loop {
+            // A clean end of input can only come before a key.
+            match input.read(&mut key[..1]) {
+                Ok(0) => return Ok(imported),
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            try!(input.read_exact(&mut key[1..]));
+            let mut len = [0; 8];
+            try!(input.read_exact(&mut len));
+            // Read rather than allocate the length up front, since it hasn't been checked.
+            let len = u64::from_le_bytes(len);
+            let mut bytes = Vec::new();
+            try!(input.by_ref().take(len).read_to_end(&mut bytes));
+            if (bytes.len() as u64) < len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated value"));
+            }
+            let data = try!(decode(&bytes));
+            match self.insert_unreferenced(data) {
+                Ok(true) => imported += 1,
+                Ok(false) => (),
+                Err(e) => return Err(io::Error::other(e)),
+            }
+        }
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// The form of the handle's key to show outside the interner. See
+    /// `Interner::exported_key`.
+    pub fn exported_key(&self) -> InternKey<N> {
+        self.interner.exported_key(self.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use {Interner, Salt};
+
+    fn encode(s: &String, out: &mut Vec<u8>) {
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<String> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    #[test]
+    fn reproducible() {
+        let words = ["delta", "alpha", "charlie", "bravo"];
+        let forwards = Interner::builder().shards(4).build();
+        let backwards = Interner::builder().shards(1).build();
+        let _f: Vec<_> = words.iter().map(|w| forwards.intern_borrowed(*w)).collect();
+        let _b: Vec<_> = words.iter().rev().map(|w| backwards.intern_borrowed(*w)).collect();
+        let _extra = forwards.intern_borrowed("alpha");
+
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        assert_eq!(forwards.export_sorted(&mut a, encode).unwrap(), 4);
+        assert_eq!(backwards.export_sorted(&mut b, encode).unwrap(), 4);
+        assert_eq!(a, b);
+
+        let copy: Interner<String> = Interner::new();
+        assert_eq!(copy.import_sorted(&a[..], decode).unwrap(), 4);
+        assert_eq!(copy.import_sorted(&a[..], decode).unwrap(), 0);
+        assert!(copy.get("charlie").is_some());
+        assert!(copy.import_sorted(&a[..a.len() - 1], decode).is_err());
+
+        // A length far beyond the end of the input is an error rather than an allocation.
+        let mut huge = a[..20].to_vec();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(copy.import_sorted(&huge[..], decode).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn export_skips_dropped() {
+        let interner = Interner::new();
+        let _a = interner.intern(String::from("a"));
+        drop(interner.intern(String::from("b")));
+        let mut out = Vec::new();
+        assert_eq!(interner.export_sorted(&mut out, encode).unwrap(), 1);
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn export_salt() {
+        let plain = Interner::new();
+        let salted = Interner::builder().export_salt(Salt::new([7; 16])).build();
+        let a = plain.intern(String::from("a"));
+        let b = salted.intern(String::from("a"));
+        assert_eq!(a.key(), b.key());
+        assert_eq!(&a.exported_key(), a.key());
+        assert!(&b.exported_key() != b.key());
+        assert!(!format!("{:?}", b).contains(&b.key().to_string()));
+
+        let mut out = Vec::new();
+        salted.export_sorted(&mut out, encode).unwrap();
+        assert_eq!(&out[..20], &b.exported_key().to_bytes()[..]);
+    }
+}
@@ -0,0 +1,128 @@
+//! Folds and rewrites over hash-consed trees that visit each distinct subtree once. Results
+//! are memoized by key, so a subtree shared by many parents is only folded the first time
+//! it's reached.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use {BuildKeyHasher, BuildMap, Hc, InternKey, Interner};
+
+/// Memoized results of folding over the nodes of an interner. Keep a `Folder` around to
+/// reuse its results across folds with the same function.
+pub struct Folder<'a, T: 'a, V, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    results: HashMap<InternKey<N>, V, BuildKeyHasher>,
+    phantom: PhantomData<Hc<'a, T, S, N>>,
+}
+
+impl<'a, T: 'a, V: Clone, S: BuildMap<T, N> + 'a, const N: usize> Folder<'a, T, V, S, N> {
+    pub fn new() -> Folder<'a, T, V, S, N> {
+        Folder {
+            results: HashMap::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Fold the tree under `root`. `f` is given a node and a function to fold its children
+    /// with, and returns the node's result. It's called once per distinct node, so it
+    /// mustn't give different results for the same node.
+    pub fn fold<F>(&mut self, root: &Hc<'a, T, S, N>, f: F) -> V
+            where F: Fn(&T, &mut dyn FnMut(&Hc<'a, T, S, N>) -> V) -> V
+    {
+        fold_node(root, &f, &mut self.results)
+    }
+
+    /// The number of nodes with memoized results.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+}
+
+impl<'a, T: 'a, V: Clone, S: BuildMap<T, N> + 'a, const N: usize> Default for Folder<'a, T, V, S, N> {
+    fn default() -> Folder<'a, T, V, S, N> {
+        Folder::new()
+    }
+}
+
+fn fold_node<'a, T, V, S, F, const N: usize>(node: &Hc<'a, T, S, N>, f: &F,
+                                             results: &mut HashMap<InternKey<N>, V, BuildKeyHasher>) -> V
+        where V: Clone,
+              S: BuildMap<T, N>,
+              F: Fn(&T, &mut dyn FnMut(&Hc<'a, T, S, N>) -> V) -> V
+{
+    if let Some(result) = results.get(node.key()) {
+        return result.clone();
+    }
+    let result = f(node, &mut |child| fold_node(child, f, results));
+    results.insert(node.key().clone(), result.clone());
+    result
+}
+
+impl<T: Hash, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Rebuild the tree under `root`. `f` is given a node and a function to rewrite its
+    /// children with, and returns the replacement node, which is interned. Each distinct
+    /// subtree is rewritten once.
+    pub fn rewrite<'a, F>(&'a self, root: &Hc<'a, T, S, N>, f: F) -> Hc<'a, T, S, N>
+            where F: Fn(&T, &mut dyn FnMut(&Hc<'a, T, S, N>) -> Hc<'a, T, S, N>) -> T
+    {
+        Folder::new().fold(root, |node, rewrite| self.intern(f(node, rewrite)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::Folder;
+    use {Hc, Interner};
+
+    #[derive(Hash)]
+    enum Expr<'a> {
+        Lit(i64),
+        Add(Hc<'a, Expr<'a>>, Hc<'a, Expr<'a>>),
+    }
+
+    #[test]
+    fn fold_and_rewrite() {
+        let interner: &'static Interner<Expr<'static>> = Box::leak(Box::new(Interner::new()));
+        let x = interner.intern(Expr::Add(interner.intern(Expr::Lit(1)), interner.intern(Expr::Lit(2))));
+        let mut root = x;
+        for _ in 0..20 {
+            root = interner.intern(Expr::Add(root.clone(), root));
+        }
+
+        let visits = Cell::new(0);
+        let mut folder = Folder::new();
+        let sum = folder.fold(&root, |node, fold| {
+            visits.set(visits.get() + 1);
+            match *node {
+                Expr::Lit(n) => n,
+                Expr::Add(ref l, ref r) => fold(l) + fold(r),
+            }
+        });
+        assert_eq!(sum, 3 << 20);
+        assert_eq!(visits.get(), 23);
+        assert_eq!(folder.len(), 23);
+
+        let doubled = interner.rewrite(&root, |node, rewrite| {
+            match *node {
+                Expr::Lit(n) => Expr::Lit(2 * n),
+                Expr::Add(ref l, ref r) => Expr::Add(rewrite(l), rewrite(r)),
+            }
+        });
+        assert_eq!(Folder::new().fold(&doubled, |node, fold| {
+            match *node {
+                Expr::Lit(n) => n,
+                Expr::Add(ref l, ref r) => fold(l) + fold(r),
+            }
+        }), 6 << 20);
+    }
+}
@@ -0,0 +1,87 @@
+//! A process-wide string interner, for code that doesn't want to thread an `Interner`
+//! through everything.
+
+use std::convert::Infallible;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use {Intern, Interned, Interner, StaticInterner};
+
+static STRINGS: StaticInterner<String> = StaticInterner::new();
+
+/// The global string interner. It's created on first use and never dropped.
+pub fn strings() -> &'static Interner<String> {
+    STRINGS.get()
+}
+
+/// Intern a value into the global string interner, ie. `"foo".interned()`.
+pub trait InternGlobal {
+    fn interned(self) -> Interned<'static, String>;
+}
+
+impl<T: Intern<String>> InternGlobal for T {
+    fn interned(self) -> Interned<'static, String> {
+        self.intern_in(strings())
+    }
+}
+
+impl<'b> From<&'b str> for Interned<'static, String> {
+    fn from(s: &'b str) -> Interned<'static, String> {
+        s.interned()
+    }
+}
+
+impl From<String> for Interned<'static, String> {
+    fn from(s: String) -> Interned<'static, String> {
+        s.interned()
+    }
+}
+
+impl FromStr for Interned<'static, String> {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Interned<'static, String>, Infallible> {
+        Ok(s.interned())
+    }
+}
+
+/// Globally interned strings serialize as plain strings, and deserialize by interning.
+#[cfg(feature = "serde")]
+impl Serialize for Interned<'static, String> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Interned<'static, String> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Interned<'static, String>, D::Error> {
+        String::deserialize(deserializer).map(InternGlobal::interned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interned;
+
+    use super::InternGlobal;
+
+    #[test]
+    fn interned() {
+        let s0 = "global".interned();
+        let s1 = String::from("global").interned();
+        assert_eq!(s0, s1);
+        assert_eq!(*s0, "global");
+    }
+
+    #[test]
+    fn conversions() {
+        let s0: Interned<'static, String> = "conversions".into();
+        let s1 = Interned::from(String::from("conversions"));
+        let s2: Interned<'static, String> = "conversions".parse().unwrap();
+        assert_eq!(s0, s1);
+        assert_eq!(s0, s2);
+    }
+}
@@ -0,0 +1,151 @@
+//! Holding every shard's lock at once, so that a sequence of operations on an interner
+//! happens atomically.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::sync::RwLockWriteGuard;
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildKeyHasher, BuildMap, InternError, InternField, InternKey, Interned, Interner, MapBackend};
+
+/// Exclusive access to a whole interner, from `Interner::lock`. Nothing else can intern into
+/// it, look anything up or reclaim anything until the guard is dropped, so interning into
+/// the same interner other than through the guard deadlocks. Handles can still be cloned
+/// and dropped.
+pub struct InternerGuard<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    maps: Vec<RwLockWriteGuard<'a, S::Map>>,
+    // Dropping a `T` can drop handles into the interner, so removed values are kept until
+    // after the locks have been released. Fields drop in order, so this has to come after
+    // `maps`.
+    removed: Vec<Box<InternField<T, N>>>,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Lock every shard, for doing several operations with nothing happening in between,
+    /// eg. checking that a set of values is interned and removing them all if none is used.
+    pub fn lock<'a>(&'a self) -> Result<InternerGuard<'a, T, S, N>, InternError> {
+        // Shards are always locked in index order, as by `clear`, so two guards can't
+        // deadlock each other.
+        let mut maps = Vec::with_capacity(self.shards.len());
+        for index in 0..self.shards.len() {
+            maps.push(try!(self.lock_shard(index)));
+        }
+        Ok(InternerGuard {
+            interner: self,
+            maps: maps,
+            removed: Vec::new(),
+        })
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> InternerGuard<'a, T, S, N> {
+    fn map(&self, key: &InternKey<N>) -> &S::Map {
+        &self.maps[self.interner.shard_index(key)]
+    }
+
+    /// A handle to the value equal to `data`, if it's interned.
+    pub fn get<B: ?Sized + Hash>(&self, data: &B) -> Option<Interned<'a, T, S, N>>
+            where T: Borrow<B>
+    {
+        let key = self.interner.key_of(data);
+        self.map(&key).get(&key).map(|field| self.interner.handle(field))
+    }
+
+    /// Intern `data`. Unlike `Interner::intern` this doesn't make room under the interner's
+    /// limits first, since that would need the locks the guard holds, so it fails if the
+    /// interner is full.
+    pub fn insert(&mut self, data: T) -> Result<Interned<'a, T, S, N>, InternError>
+            where T: Hash
+    {
+        let interner = self.interner;
+        interner.note_intern();
+        let key = interner.key_of(&data);
+        let index = interner.shard_index(&key);
+        if let Some(field) = self.maps[index].get(&key) {
+            // Keep `data` to be dropped once the locks are released.
+            self.removed.push(Box::new(InternField::new(key.clone(), data)));
+            return Ok(interner.handle(field));
+        }
+        let field = Box::new(InternField::new(key, data));
+        if let Err(e) = interner.admit(&field) {
+            self.removed.push(field);
+            return Err(e);
+        }
+        let (field, _) = self.maps[index].get_or_insert(field);
+        interner.inserted(field);
+        Ok(interner.new_handle(field))
+    }
+
+    /// Remove the value equal to `data` if it's interned and has no handles, returning
+    /// whether it was removed.
+    pub fn remove_if_unused<B: ?Sized + Hash>(&mut self, data: &B) -> bool
+            where T: Borrow<B>
+    {
+        let interner = self.interner;
+        let key = interner.key_of(data);
+        let map = &mut self.maps[interner.shard_index(&key)];
+        let unused = match map.get(&key) {
+            Some(field) => 0 == field.count.load(Acquire),
+            None => false,
+        };
+        if !unused {
+            return false;
+        }
+        match map.remove(&key) {
+            Some(field) => {
+                interner.removed(&field);
+                self.removed.push(field);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Every interned value with its key, including those with no handles left, in no
+    /// particular order.
+    pub fn iter<'g>(&'g self) -> Box<dyn Iterator<Item=(&'g InternKey<N>, &'g T)> + 'g> {
+        Box::new(self.maps.iter()
+                          .flat_map(|map| map.iter())
+                          .map(|field| (&field.key, &field.data)))
+    }
+
+    /// The number of entries in the interner.
+    pub fn len(&self) -> usize {
+        self.maps.iter().map(|map| map.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn lock() {
+        let interner = Interner::builder().shards(4).mark_and_sweep().build();
+        drop(interner.intern(String::from("a")));
+        let b = interner.intern(String::from("b"));
+        {
+            let mut guard = interner.lock().unwrap();
+            assert!(guard.get("a").is_some());
+            assert!(guard.get("c").is_none());
+            let c = guard.insert(String::from("c")).unwrap();
+            assert_eq!(guard.insert(String::from("c")).unwrap(), c);
+            assert!(!guard.remove_if_unused("b"));
+            assert!(!guard.remove_if_unused("c"));
+            drop(c);
+            assert!(guard.remove_if_unused("a"));
+            assert!(!guard.remove_if_unused("a"));
+            let mut values: Vec<&str> = guard.iter().map(|(_, s)| &s[..]).collect();
+            values.sort();
+            assert_eq!(values, ["b", "c"]);
+            assert_eq!(guard.len(), 2);
+        }
+        assert_eq!(interner.get("b"), Some(b));
+        assert!(interner.get("a").is_none());
+    }
+}
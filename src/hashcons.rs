@@ -0,0 +1,73 @@
+//! Building maximally-shared trees. A node type whose children are handles implements
+//! `HashConsed` to say how to build itself from a plain tree, and `Interner::cons` then
+//! interns a whole tree bottom-up in one call.
+
+use std::hash::Hash;
+
+use {BuildKeyHasher, BuildMap, Interned, Interner};
+
+/// A handle to a hash-consed node. Equal subtrees are always the same handle, so comparing
+/// and hashing them is O(1).
+pub type Hc<'a, T, S = BuildKeyHasher, const N: usize = 20> = Interned<'a, T, S, N>;
+
+/// A node type whose children are `Hc` handles into an interner of the same type.
+pub trait HashConsed<'a, S: BuildMap<Self, N> = BuildKeyHasher, const N: usize = 20>: Hash + Sized + 'a {
+    /// The unshared form of the tree, eg. an enum with `Box`ed children.
+    type Term;
+
+    /// Build a node from `term`, consing its children with `interner`. Implementations
+    /// normally just call `interner.cons` on each child.
+    fn cons_children(term: Self::Term, interner: &'a Interner<Self, S, N>) -> Self;
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Intern `term`'s children and then the node built from them.
+    pub fn cons<'a>(&'a self, term: T::Term) -> Hc<'a, T, S, N>
+            where T: HashConsed<'a, S, N>
+    {
+        self.intern(T::cons_children(term, self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hc, HashConsed};
+    use Interner;
+
+    enum Ast {
+        Lit(i64),
+        Add(Box<Ast>, Box<Ast>),
+    }
+
+    #[derive(Hash)]
+    enum Expr<'a> {
+        Lit(i64),
+        Add(Hc<'a, Expr<'a>>, Hc<'a, Expr<'a>>),
+    }
+
+    impl<'a> HashConsed<'a> for Expr<'a> {
+        type Term = Ast;
+
+        fn cons_children(term: Ast, interner: &'a Interner<Expr<'a>>) -> Expr<'a> {
+            match term {
+                Ast::Lit(n) => Expr::Lit(n),
+                Ast::Add(l, r) => Expr::Add(interner.cons(*l), interner.cons(*r)),
+            }
+        }
+    }
+
+    fn sum() -> Ast {
+        Ast::Add(Box::new(Ast::Lit(1)), Box::new(Ast::Lit(2)))
+    }
+
+    #[test]
+    fn shares_subtrees() {
+        let interner: &'static Interner<Expr<'static>> = Box::leak(Box::new(Interner::new()));
+        let expr = interner.cons(Ast::Add(Box::new(sum()), Box::new(sum())));
+        match *expr {
+            Expr::Add(ref l, ref r) => assert!(l == r),
+            Expr::Lit(..) => panic!("expected an addition"),
+        }
+        assert_eq!(interner.len(), 4);
+    }
+}
@@ -0,0 +1,130 @@
+//! Checking an interner's table against its own invariants, for tracking down corruption.
+
+use std::hash::Hash;
+use std::ptr;
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildMap, InternError, InternKey, Interner, MapBackend};
+
+/// What `Interner::verify_integrity` found. Keys are sorted.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct IntegrityReport<const N: usize = 20> {
+    /// The number of entries in the table.
+    pub entries: usize,
+    /// The number of entries according to the interner's statistics.
+    pub counted_entries: usize,
+    /// Entries stored in a shard other than the one their key belongs in.
+    pub misplaced: Vec<InternKey<N>>,
+    /// Entries which a lookup of their own key doesn't find, including misplaced ones.
+    pub unreachable: Vec<InternKey<N>>,
+    /// Entries whose values no longer hash to their keys. Only checked in debug builds.
+    pub rekeyed: Vec<InternKey<N>>,
+    /// Entries with no handles, outside cache mode. These aren't necessarily a problem: they
+    /// may be waiting to be reclaimed, or have been added without handles by an import or
+    /// `drain_into`. But any left after `Interner::reclaim` that weren't are leaks.
+    pub unused: Vec<InternKey<N>>,
+    /// Entries pinned more times than they have handles.
+    pub overpinned: Vec<InternKey<N>>,
+}
+
+impl<const N: usize> IntegrityReport<N> {
+    /// Whether no invariant is broken. Unused entries don't count.
+    pub fn is_ok(&self) -> bool {
+        self.entries == self.counted_entries
+            && self.misplaced.is_empty()
+            && self.unreachable.is_empty()
+            && self.rekeyed.is_empty()
+            && self.overpinned.is_empty()
+    }
+}
+
+impl<T: Hash, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Check the table with every shard locked. Values built by `intern_borrowed_with` are
+    /// keyed by what they were built from, so they're reported as rekeyed unless they hash
+    /// the same way. Handle counts can't be checked against the handles themselves, since
+    /// the interner doesn't know where they are.
+    pub fn verify_integrity(&self) -> Result<IntegrityReport<N>, InternError> {
+        let mut maps = Vec::with_capacity(self.shards.len());
+        for index in 0..self.shards.len() {
+            maps.push(try!(self.lock_shard(index)));
+        }
+        let mut report = IntegrityReport {
+            counted_entries: self.stats().entries,
+            ..IntegrityReport::default()
+        };
+        for (index, map) in maps.iter().enumerate() {
+            for field in map.iter() {
+                report.entries += 1;
+                let key = &field.key;
+                if self.shard_index(key) != index {
+                    report.misplaced.push(key.clone());
+                }
+                if !maps[self.shard_index(key)].get(key).is_some_and(|found| ptr::eq(found, field)) {
+                    report.unreachable.push(key.clone());
+                }
+                if cfg!(debug_assertions) && self.key_of(&field.data) != *key {
+                    report.rekeyed.push(key.clone());
+                }
+                let count = field.count.load(Acquire);
+                if !self.mark_and_sweep && 0 == count {
+                    report.unused.push(key.clone());
+                }
+                if field.pinned.load(Acquire) > count {
+                    report.overpinned.push(key.clone());
+                }
+            }
+        }
+        drop(maps);
+        report.misplaced.sort();
+        report.unreachable.sort();
+        report.rekeyed.sort();
+        report.unused.sort();
+        report.overpinned.sort();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+    use std::sync::atomic::Ordering::Relaxed;
+
+    use {InternField, Interner, MapBackend};
+
+    #[test]
+    fn verify_integrity() {
+        let interner = Interner::builder().shards(4).build();
+        let _a = interner.intern(String::from("a"));
+        drop(interner.intern(String::from("b")));
+        let report = interner.verify_integrity().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.entries, 2);
+        assert_eq!(report.unused.len(), 1);
+        interner.reclaim().unwrap();
+        let report = interner.verify_integrity().unwrap();
+        assert!(report.is_ok());
+        assert!(report.unused.is_empty());
+
+        // Corrupt the table by putting an entry in the wrong shard, behind the counters' backs.
+        let key = interner.key_of("c");
+        let wrong = (interner.shard_index(&key) + 1) % 4;
+        interner.shards[wrong].write().unwrap().get_or_insert(Box::new(InternField::new(key.clone(), String::from("c"))));
+        let report = interner.verify_integrity().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!((report.entries, report.counted_entries), (2, 1));
+        assert_eq!(report.misplaced, slice::from_ref(&key));
+        assert_eq!(report.unreachable, slice::from_ref(&key));
+        assert!(report.rekeyed.is_empty());
+        assert_eq!(report.unused, [key]);
+
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        a.clone().pin();
+        assert!(interner.verify_integrity().unwrap().overpinned.is_empty());
+        // Corrupt the entry's pin count so that it has more pins than handles.
+        a.field.pinned.store(3, Relaxed);
+        let report = interner.verify_integrity().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.overpinned, [a.key().clone()]);
+    }
+}
@@ -0,0 +1,118 @@
+//! Side tables keyed by handles. Entries can be looked up by key alone, eg. a key received
+//! over the wire, without first getting a handle to the value.
+
+use std::collections::hash_map::{self, HashMap};
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned};
+
+/// A map from handles to values of type `V`. Holding the handles keeps their values
+/// interned for as long as they're in the map.
+pub struct InternedMap<'a, T: 'a, V, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    map: HashMap<Interned<'a, T, S, N>, V, BuildKeyHasher>,
+}
+
+impl<'a, T: 'a, V, S: BuildMap<T, N> + 'a, const N: usize> InternedMap<'a, T, V, S, N> {
+    pub fn new() -> InternedMap<'a, T, V, S, N> {
+        InternedMap {
+            map: HashMap::default(),
+        }
+    }
+
+    /// Insert `value` for `handle`, returning the value it replaced.
+    pub fn insert(&mut self, handle: Interned<'a, T, S, N>, value: V) -> Option<V> {
+        self.map.insert(handle, value)
+    }
+
+    pub fn get(&self, key: &InternKey<N>) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &InternKey<N>) -> Option<&mut V> {
+        self.map.get_mut(key)
+    }
+
+    /// The handle stored under `key` along with its value.
+    pub fn get_key_value(&self, key: &InternKey<N>) -> Option<(&Interned<'a, T, S, N>, &V)> {
+        self.map.get_key_value(key)
+    }
+
+    pub fn contains_key(&self, key: &InternKey<N>) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Remove the entry for `key`, giving back its handle and value.
+    pub fn remove(&mut self, key: &InternKey<N>) -> Option<(Interned<'a, T, S, N>, V)> {
+        self.map.remove_entry(key)
+    }
+
+    pub fn entry(&mut self, handle: Interned<'a, T, S, N>) -> hash_map::Entry<'_, Interned<'a, T, S, N>, V> {
+        self.map.entry(handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, Interned<'a, T, S, N>, V> {
+        self.map.iter()
+    }
+}
+
+impl<'a, T: 'a, V, S: BuildMap<T, N> + 'a, const N: usize> Default for InternedMap<'a, T, V, S, N> {
+    fn default() -> InternedMap<'a, T, V, S, N> {
+        InternedMap::new()
+    }
+}
+
+impl<'a, T: 'a, V, S: BuildMap<T, N> + 'a, const N: usize> IntoIterator for InternedMap<'a, T, V, S, N> {
+    type Item = (Interned<'a, T, S, N>, V);
+    type IntoIter = hash_map::IntoIter<Interned<'a, T, S, N>, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::InternedMap;
+    use {InternKey, Interner};
+
+    #[test]
+    // Handles hash by their keys, which never change.
+    #[allow(clippy::mutable_key_type)]
+    fn lookup_by_key() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let mut map = InternedMap::new();
+        assert_eq!(map.insert(a.clone(), 1), None);
+        assert_eq!(map.insert(interner.intern(String::from("b")), 2), None);
+        assert_eq!(map.insert(a.clone(), 3), Some(1));
+
+        let wire = InternKey::from_bytes(a.key().to_bytes());
+        assert_eq!(map.get(&wire), Some(&3));
+        *map.get_mut(&interner.key_of("b")).unwrap() += 1;
+        assert_eq!(map.get(&interner.key_of("b")), Some(&3));
+        assert!(!map.contains_key(&interner.key_of("c")));
+
+        let mut set = HashSet::new();
+        set.insert(a.clone());
+        assert!(set.contains(&wire));
+
+        drop(a);
+        drop(set);
+        assert_eq!(interner.len(), 2);
+        let (handle, value) = map.remove(&wire).unwrap();
+        assert_eq!((&handle[..], value), ("a", 3));
+    }
+}
@@ -0,0 +1,107 @@
+//! Handles narrowed to part of their value, like `cell::Ref::map`.
+
+use std::fmt;
+use std::ops::Deref;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned};
+
+/// A reference to part of an interned value, which holds a handle to the whole so that the
+/// entry stays alive. Made by `Interned::map`.
+pub struct InternedRef<'a, T: 'a, U: ?Sized + 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    handle: Interned<'a, T, S, N>,
+    data: &'a U,
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// Narrow the handle to a part of its value, such as one field of a struct, without
+    /// cloning it.
+    pub fn map<U: ?Sized, F>(self, f: F) -> InternedRef<'a, T, U, S, N>
+            where F: FnOnce(&T) -> &U
+    {
+        let field = self.field;
+        InternedRef {
+            data: f(&field.data),
+            handle: self,
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized, S: BuildMap<T, N>, const N: usize> InternedRef<'a, T, U, S, N> {
+    /// Narrow the reference further.
+    pub fn map<V: ?Sized, F>(self, f: F) -> InternedRef<'a, T, V, S, N>
+            where F: FnOnce(&U) -> &V
+    {
+        InternedRef {
+            data: f(self.data),
+            handle: self.handle,
+        }
+    }
+
+    /// The handle to the whole value.
+    pub fn handle(&self) -> &Interned<'a, T, S, N> {
+        &self.handle
+    }
+
+    /// The key the whole value is interned under.
+    pub fn key(&self) -> &InternKey<N> {
+        self.handle.key()
+    }
+}
+
+impl<'a, T, U: ?Sized, S: BuildMap<T, N>, const N: usize> Deref for InternedRef<'a, T, U, S, N> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        self.data
+    }
+}
+
+impl<'a, T, U: ?Sized, S: BuildMap<T, N>, const N: usize> Clone for InternedRef<'a, T, U, S, N> {
+    fn clone(&self) -> InternedRef<'a, T, U, S, N> {
+        InternedRef {
+            handle: self.handle.clone(),
+            data: self.data,
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized + fmt::Debug, S: BuildMap<T, N>, const N: usize> fmt::Debug for InternedRef<'a, T, U, S, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "InternedRef[{}] ", self.handle.key()));
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn map() {
+        let interner = Interner::new();
+        let item = interner.intern(Item {
+            name: String::from("widget"),
+            tags: vec![String::from("blue"), String::from("small")],
+        });
+        let key = item.key().clone();
+        let name = item.map(|item| &item.name[..]);
+        assert_eq!(&*name, "widget");
+        let tag = name.clone().handle().clone().map(|item| &item.tags).map(|tags| &tags[1]);
+        assert_eq!(*tag, "small");
+        drop(name);
+
+        // The projection keeps the entry alive.
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 1);
+        assert_eq!(tag.key(), &key);
+        drop(tag);
+        interner.reclaim().unwrap();
+        assert!(interner.is_empty());
+    }
+}
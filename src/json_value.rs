@@ -0,0 +1,82 @@
+//! Deduplicating the strings in parsed JSON. Documents with the same shape repeat the same
+//! object keys (and often the same values) over and over, and `serde_json::Value` gives
+//! each occurrence its own allocation.
+
+use serde_json::{Map, Number, Value};
+
+use {BuildKeyHasher, BuildMap, Interned, Interner};
+
+/// A JSON value whose strings, including object keys, are interned.
+#[derive(Clone, PartialEq, Debug)]
+pub enum JsonValue<'a, S: BuildMap<String, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(Interned<'a, String, S, N>),
+    Array(Vec<JsonValue<'a, S, N>>),
+    /// An object's members, in the order the `Value` iterated them in.
+    Object(Vec<(Interned<'a, String, S, N>, JsonValue<'a, S, N>)>),
+}
+
+impl<S: BuildMap<String, N>, const N: usize> Interner<String, S, N> {
+    /// Intern every string in `value`, keys and values alike.
+    pub fn intern_json<'a>(&'a self, value: &Value) -> JsonValue<'a, S, N> {
+        match *value {
+            Value::Null => JsonValue::Null,
+            Value::Bool(b) => JsonValue::Bool(b),
+            Value::Number(ref n) => JsonValue::Number(n.clone()),
+            Value::String(ref s) => JsonValue::String(self.intern_borrowed(&s[..])),
+            Value::Array(ref values) => {
+                JsonValue::Array(values.iter().map(|value| self.intern_json(value)).collect())
+            },
+            Value::Object(ref members) => {
+                JsonValue::Object(members.iter()
+                                         .map(|(key, value)| {
+                                             (self.intern_borrowed(&key[..]), self.intern_json(value))
+                                         })
+                                         .collect())
+            },
+        }
+    }
+}
+
+impl<'a, S: BuildMap<String, N> + 'a, const N: usize> JsonValue<'a, S, N> {
+    /// Copy back out into an ordinary `Value`.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            JsonValue::Null => Value::Null,
+            JsonValue::Bool(b) => Value::Bool(b),
+            JsonValue::Number(ref n) => Value::Number(n.clone()),
+            JsonValue::String(ref s) => Value::String((**s).clone()),
+            JsonValue::Array(ref values) => Value::Array(values.iter().map(JsonValue::to_value).collect()),
+            JsonValue::Object(ref members) => {
+                let mut map = Map::new();
+                for &(ref key, ref value) in members {
+                    map.insert((**key).clone(), value.to_value());
+                }
+                Value::Object(map)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use Interner;
+
+    #[test]
+    fn intern_json() {
+        let interner = Interner::new();
+        let docs: Vec<Value> = (0..3).map(|i| {
+            serde_json::from_str(&format!("{{\"name\": \"n{}\", \"tags\": [\"a\", \"b\"]}}", i)).unwrap()
+        }).collect();
+        let interned: Vec<_> = docs.iter().map(|doc| interner.intern_json(doc)).collect();
+        // "name", "tags", "a", "b" and the three names.
+        assert_eq!(interner.len(), 7);
+        for (doc, value) in docs.iter().zip(&interned) {
+            assert_eq!(&value.to_value(), doc);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Export and import of an interner's contents as JSON Lines, one entry per line.
+
+use std::hash::Hash;
+use std::io;
+use std::sync::atomic::Ordering::Relaxed;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use {BuildMap, Interner, MapBackend};
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Write every entry to `out` as a line of the form
+    /// `{"key":"...","count":N,"value":...}`. Lines are sorted by key so exports can be diffed.
+    pub fn export_jsonl<W: io::Write>(&self, mut out: W) -> io::Result<()>
+            where T: Serialize
+    {
+        let mut lines = Vec::new();
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            for field in map.iter() {
+                let value = try!(serde_json::to_string(&field.data));
+                let key = self.exported_key(&field.key);
+                let line = format!("{{\"key\":\"{}\",\"count\":{},\"value\":{}}}",
+                                   key, field.count.load(Relaxed), value);
+                lines.push((key, line));
+            }
+        }
+        lines.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, line) in lines {
+            try!(writeln!(out, "{}", line));
+        }
+        Ok(())
+    }
+
+    /// Read entries written by `export_jsonl` back in, returning how many were new. Imported
+    /// values are re-keyed for this interner and don't have any handles. Blank lines are
+    /// skipped.
+    pub fn import_jsonl<R: io::BufRead>(&self, input: R) -> io::Result<usize>
+            where T: DeserializeOwned + Hash
+    {
+        let mut imported = 0;
+        for line in input.lines() {
+            let line = try!(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut entry: Value = try!(serde_json::from_str(&line));
+            let value = match entry.get_mut("value") {
+                Some(value) => value.take(),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "entry has no value")),
+            };
+            let data: T = try!(serde_json::from_value(value));
+            match self.insert_unreferenced(data) {
+                Ok(true) => imported += 1,
+                Ok(false) => (),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            }
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn round_trip() {
+        let interner = Interner::new();
+        let _a = interner.intern(String::from("a"));
+        let _b = interner.intern(String::from("b\n\"quoted\""));
+        let mut out = Vec::new();
+        interner.export_jsonl(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("\"count\":1"));
+
+        let copy: Interner<String> = Interner::new();
+        assert_eq!(copy.import_jsonl(text.as_bytes()).unwrap(), 2);
+        assert_eq!(copy.import_jsonl(text.as_bytes()).unwrap(), 0);
+        assert_eq!(*copy.get("b\n\"quoted\"").unwrap(), "b\n\"quoted\"");
+    }
+}
@@ -0,0 +1,71 @@
+//! Serde support for keys, as their raw bytes rather than a struct.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, SeqAccess, Visitor};
+
+use InternKey;
+
+impl<const N: usize> Serialize for InternKey<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for InternKey<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<InternKey<N>, D::Error> {
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+struct KeyVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for KeyVisitor<N> {
+    type Value = InternKey<N>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bytes", N)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<InternKey<N>, E> {
+        if v.len() != N {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(v);
+        Ok(InternKey::from_bytes(bytes))
+    }
+
+    // Formats without a native byte string type (eg. JSON) write bytes as a sequence.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<InternKey<N>, A::Error> {
+        let mut bytes = [0; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = match try!(seq.next_element()) {
+                Some(byte) => byte,
+                None => return Err(de::Error::invalid_length(i, &self)),
+            };
+        }
+        if try!(seq.next_element::<u8>()).is_some() {
+            return Err(de::Error::invalid_length(N + 1, &self));
+        }
+        Ok(InternKey::from_bytes(bytes))
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use serde_json;
+
+    use {InternKey, Interner};
+
+    #[test]
+    fn round_trip() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let json = serde_json::to_string(a.key()).unwrap();
+        let key: InternKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(&key, a.key());
+        assert!(serde_json::from_str::<InternKey>("[1,2,3]").is_err());
+    }
+}
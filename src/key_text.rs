@@ -0,0 +1,91 @@
+//! Text encodings of keys, for putting them in URLs, file names and the like. Unlike
+//! `Display`, which writes each word in hex without leading zeros, these encode every byte
+//! and so always give the same length for the same key width.
+
+use InternKey;
+
+const BASE32: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE64URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encode `bytes` a few bits at a time from `alphabet`, most significant bits first, padding
+/// the last character out with zero bits. `bits` is the width of each character.
+fn encode(bytes: &[u8], alphabet: &[u8], bits: u32) -> String {
+    let mask = (1 << bits) - 1;
+    let mut text = String::with_capacity((bytes.len() * 8).div_ceil(bits as usize));
+    let mut acc = 0u32;
+    let mut nbits = 0;
+    for &byte in bytes {
+        acc = (acc << 8) | byte as u32;
+        nbits += 8;
+        while nbits >= bits {
+            nbits -= bits;
+            text.push(alphabet[(acc >> nbits) as usize & mask] as char);
+        }
+    }
+    if nbits > 0 {
+        text.push(alphabet[(acc << (bits - nbits)) as usize & mask] as char);
+    }
+    text
+}
+
+/// RFC 4648 base32 in lower case, without padding.
+pub(crate) fn base32(bytes: &[u8]) -> String {
+    encode(bytes, BASE32, 5)
+}
+
+impl<const N: usize> InternKey<N> {
+    /// The key's bytes in lower case hex, two digits each.
+    pub fn to_hex(&self) -> String {
+        encode(&self.data, b"0123456789abcdef", 4)
+    }
+
+    /// Read a key back from `to_hex`'s encoding, in either case.
+    pub fn from_hex(text: &str) -> Option<InternKey<N>> {
+        // `from_str_radix` would also take a sign, which `to_hex` never writes.
+        if text.len() != 2 * N || !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = [0; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = match u8::from_str_radix(&text[2 * i..2 * i + 2], 16) {
+                Ok(byte) => byte,
+                Err(..) => return None,
+            };
+        }
+        Some(InternKey::from_bytes(bytes))
+    }
+
+    /// The key in RFC 4648 base32, lower case and unpadded, which is safe in file names on
+    /// case-insensitive file systems.
+    pub fn to_base32(&self) -> String {
+        base32(&self.data)
+    }
+
+    /// The key in RFC 4648 URL-safe base64, unpadded. The shortest of the encodings.
+    pub fn to_base64url(&self) -> String {
+        encode(&self.data, BASE64URL, 6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use InternKey;
+
+    #[test]
+    fn encodings() {
+        let mut bytes = [0; 20];
+        bytes[0] = 0x0f;
+        bytes[19] = 0xff;
+        let key = InternKey::from_bytes(bytes);
+        assert_eq!(key.to_hex(), "0f000000000000000000000000000000000000ff");
+        assert_eq!(InternKey::from_hex(&key.to_hex().to_uppercase()), Some(key.clone()));
+        assert_eq!(InternKey::<20>::from_hex("0f"), None);
+        assert_eq!(InternKey::<8>::from_hex("+f00000000000000"), None);
+        assert_eq!(key.to_base32(), "b4aaaaaaaaaaaaaaaaaaaaaaaaaaaah7");
+        assert_eq!(key.to_base64url(), "DwAAAAAAAAAAAAAAAAAAAAAAAP8");
+
+        // RFC 4648's test vectors.
+        assert_eq!(super::base32(b"foobar"), "mzxw6ytboi");
+        assert_eq!(super::encode(b"foob", super::BASE64URL, 6), "Zm9vYg");
+    }
+}
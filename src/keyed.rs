@@ -0,0 +1,128 @@
+//! Symbol tables: interners whose entries are deduplicated by a key but also carry mutable
+//! data of their own, such as a symbol's type or definition site.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard};
+
+use {BuildKeyHasher, BuildMap, Interned, Interner, MapBackend};
+
+/// An entry of a `KeyedInterner`: a key `K`, which is what's interned, and a payload `V`
+/// behind a lock. Hashes, compares and dereferences as its key alone.
+pub struct Keyed<K, V> {
+    key: K,
+    payload: Mutex<V>,
+}
+
+/// An interner of `K`s, each with a mutable `V` attached.
+pub type KeyedInterner<K, V, S = BuildKeyHasher, const N: usize = 20> = Interner<Keyed<K, V>, S, N>;
+
+impl<K, V> Keyed<K, V> {
+    pub fn new(key: K, payload: V) -> Keyed<K, V> {
+        Keyed {
+            key: key,
+            payload: Mutex::new(payload),
+        }
+    }
+
+    /// Lock the payload. A payload whose lock was poisoned by a panic is handed out anyway,
+    /// since the entry itself is still sound.
+    pub fn payload<'a>(&'a self) -> MutexGuard<'a, V> {
+        match self.payload.lock() {
+            Ok(payload) => payload,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl<K, V> Deref for Keyed<K, V> {
+    type Target = K;
+
+    fn deref(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K, V> Borrow<K> for Keyed<K, V> {
+    fn borrow(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K: Hash, V> Hash for Keyed<K, V> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.key.hash(hasher)
+    }
+}
+
+impl<K: PartialEq, V> PartialEq for Keyed<K, V> {
+    fn eq(&self, other: &Keyed<K, V>) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Eq, V> Eq for Keyed<K, V> {}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Keyed<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keyed")
+         .field("key", &self.key)
+         .field("payload", &*self.payload())
+         .finish()
+    }
+}
+
+impl<K: Hash, V, S: BuildMap<Keyed<K, V>, N>, const N: usize> Interner<Keyed<K, V>, S, N> {
+    /// Intern `key`, attaching the payload made by `init` if it's new. An existing entry
+    /// keeps its payload, and `init` isn't called.
+    pub fn intern_keyed<'a, F>(&'a self, key: K, init: F) -> Interned<'a, Keyed<K, V>, S, N>
+            where F: FnOnce() -> V,
+                  K: 'a,
+                  V: 'a
+    {
+        let hash_key = self.key_of(&key);
+        self.intern_with(hash_key, move || Keyed::new(key, init()))
+    }
+
+    /// Get the entry for `key`, if it's interned, without adding one.
+    pub fn get_keyed<'a, Q: ?Sized + Hash>(&'a self, key: &Q) -> Option<Interned<'a, Keyed<K, V>, S, N>>
+            where K: Borrow<Q> + 'a,
+                  V: 'a
+    {
+        let hash_key = self.key_of(key);
+        let map = self.read(&hash_key).unwrap();
+        map.get(&hash_key).map(|field| self.handle(field))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyedInterner;
+    use Interner;
+
+    #[derive(Debug, PartialEq)]
+    struct Symbol {
+        uses: usize,
+        ty: Option<&'static str>,
+    }
+
+    #[test]
+    fn symbol_table() {
+        let table: KeyedInterner<String, Symbol> = Interner::new();
+        let new = || Symbol { uses: 0, ty: None };
+        let x = table.intern_keyed(String::from("x"), new);
+        x.payload().ty = Some("i32");
+        for _ in 0..3 {
+            table.intern_keyed(String::from("x"), || panic!("x is already interned")).payload().uses += 1;
+        }
+        assert_eq!(*x.payload(), Symbol { uses: 3, ty: Some("i32") });
+        assert_eq!(&***x, "x");
+        assert_eq!(table.get_keyed("x"), Some(x.clone()));
+        assert!(table.get_keyed("y").is_none());
+        let y = table.intern_keyed(String::from("y"), new);
+        assert!(x != y);
+        assert_eq!(table.len(), 2);
+    }
+}
@@ -0,0 +1,131 @@
+//! Reporting entries which still have handles when their interner is dropped.
+//!
+//! Handles borrow their interner, so the only way for one to outlive it is to have been
+//! leaked, eg. with `mem::forget` or by a reference cycle through `Rc`. Such entries' counts
+//! never reach zero, which is otherwise silent. Entries pinned by `preload`, `intern_permanent`
+//! or `Interned::pin` are meant to stay, so their pins aren't reported.
+
+use std::fmt;
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildMap, InternKey, Interner, MapBackend};
+
+/// An entry whose handles were leaked.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Leak<const N: usize = 20> {
+    pub key: InternKey<N>,
+    /// The number of handles that were never dropped.
+    pub count: usize,
+    /// The value's `Debug` output.
+    pub value: String,
+}
+
+/// The callback set with `InternerBuilder::on_leak`, and how to describe values for it.
+pub(crate) struct LeakHook<T, const N: usize> {
+    describe: fn(&T) -> String,
+    report: Box<dyn Fn(Vec<Leak<N>>) + Send + Sync>,
+}
+
+impl<T, const N: usize> LeakHook<T, N> {
+    pub(crate) fn new<F>(report: F) -> LeakHook<T, N>
+            where F: Fn(Vec<Leak<N>>) + Send + Sync + 'static,
+                  T: fmt::Debug
+    {
+        LeakHook {
+            describe: describe::<T>,
+            report: Box::new(report),
+        }
+    }
+}
+
+fn describe<T: fmt::Debug>(data: &T) -> String {
+    format!("{:?}", data)
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Drop for Interner<T, S, N> {
+    fn drop(&mut self) {
+        let hook = match self.on_leak.take() {
+            Some(hook) => hook,
+            None => return,
+        };
+        let mut leaks = Vec::new();
+        for shard in self.shards.iter_mut() {
+            let map = match shard.get_mut() {
+                Ok(map) => map,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            for field in map.iter() {
+                // Pinned handles were given up on purpose.
+                let count = field.count.load(Acquire).saturating_sub(field.pinned.load(Acquire));
+                if count > 0 {
+                    leaks.push(Leak {
+                        key: field.key.clone(),
+                        count: count,
+                        value: (hook.describe)(&field.data),
+                    });
+                }
+            }
+        }
+        if !leaks.is_empty() {
+            leaks.sort_by(|a, b| a.key.cmp(&b.key));
+            (hook.report)(leaks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+    use std::mem;
+    use std::sync::{Arc, Mutex};
+
+    use Interner;
+
+    #[test]
+    fn on_leak() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let log = reports.clone();
+        let interner = Interner::builder().on_leak(move |leaks| log.lock().unwrap().push(leaks)).build();
+        let a = interner.intern(String::from("a"));
+        mem::forget(a.clone());
+        mem::forget(a.clone());
+        let _b = interner.intern(String::from("b"));
+        let key = a.key().clone();
+        drop(a);
+        drop(_b);
+        drop(interner);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].len(), 1);
+        assert_eq!(reports[0][0].key, key);
+        assert_eq!(reports[0][0].count, 2);
+        assert_eq!(reports[0][0].value, "\"a\"");
+
+        // Nothing is reported for an interner without leaks.
+        let interner = Interner::builder().on_leak(|_| panic!("nothing leaked")).build();
+        drop(interner.intern(String::from("a")));
+    }
+
+    #[test]
+    fn pins_arent_leaks() {
+        let interner = Interner::builder().on_leak(|_| panic!("nothing leaked")).build();
+        interner.preload(vec![String::from("fn"), String::from("let")]).unwrap();
+        interner.intern_permanent(String::from("fn"));
+        let _let = interner.intern(String::from("let"));
+    }
+    #[test]
+    fn on_leak_with_other_options() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let log = reports.clone();
+        let interner = Interner::builder_with_key_bytes::<12>()
+                                .on_leak(move |leaks| log.lock().unwrap().push(leaks))
+                                .hasher(RandomState::new())
+                                .build();
+        let a = interner.intern(String::from("a"));
+        let key = a.key().clone();
+        mem::forget(a);
+        drop(interner);
+        assert_eq!(reports.lock().unwrap()[0][0].key, key);
+    }
+}
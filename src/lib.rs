@@ -1,115 +1,1369 @@
 extern crate crypto;
 
+mod access;
+mod api;
+mod backend;
+mod arc;
+mod builder;
+mod stable_id;
+mod stats;
+mod static_interner;
+#[macro_use]
+mod static_symbols;
+mod symbol;
+mod cache;
+mod cas;
+mod cow;
+mod deep;
+mod delta;
+mod digest;
+mod dot;
+mod dump;
+mod entry;
+mod epoch;
+mod evict;
+mod export;
+mod fold;
+mod guard;
+mod hashcons;
+mod integrity;
+mod interned_map;
+mod interned_ref;
+mod key_text;
+mod keyed;
+mod leak;
+mod local;
+mod memo;
+mod noop;
+mod pair;
+mod partition;
+mod phf;
+mod preload;
+mod rodeo;
+mod seq;
+mod set_ops;
+mod snapshot;
+mod tokens;
+mod trim;
+mod view;
+pub use access::AccessStats;
+use access::AccessTable;
+use phf::PerfectHash;
+pub use api::InternerApi;
+pub use backend::{BTreeBackend, BuildMap, MapBackend, SharedHasher};
+pub use builder::InternerBuilder;
+pub use cas::CasDir;
+pub use deep::{InternDeep, InternInto};
+pub use delta::{Delta, DeltaMark};
+pub use digest::KeyDigest;
+pub use dot::write_dot;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use evict::{EvictionPolicy, EvictionReason, Lru};
+pub use fold::Folder;
+pub use guard::InternerGuard;
+pub use hashcons::{Hc, HashConsed};
+pub use integrity::IntegrityReport;
+pub use interned_map::InternedMap;
+pub use interned_ref::InternedRef;
+pub use keyed::{Keyed, KeyedInterner};
+pub use leak::Leak;
+use leak::LeakHook;
+pub use local::{LocalInterned, LocalInterner, RcInterned};
+pub use memo::Memo;
+pub use seq::Seq;
+pub use stable_id::StableId;
+use stable_id::StableIds;
+pub use stats::Stats;
+pub use static_interner::StaticInterner;
+pub use symbol::Symbol;
+pub use trim::Trimmer;
+pub use view::InternerView;
+use symbol::Symbols;
+use stats::Counters;
+pub use cache::LocalCache;
+pub use noop::NoopInterner;
+pub use rodeo::{Resolver, Rodeo, RodeoResolver, Spur};
+
+#[cfg(feature = "dashmap")]
+extern crate dashmap;
+#[cfg(feature = "egg")]
+extern crate egg;
+#[cfg(feature = "memmap2")]
+extern crate memmap2;
+#[cfg(feature = "quickcheck")]
+extern crate quickcheck;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "string_cache")]
+extern crate string_cache;
+#[cfg(feature = "string-interner")]
+extern crate string_interner;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+
+#[cfg(feature = "dag-cbor")]
+pub mod dag_cbor;
+#[cfg(feature = "dashmap")]
+mod dashmap_backend;
+#[cfg(feature = "dashmap")]
+pub use dashmap_backend::DashMapBackend;
+#[cfg(feature = "debug-handles")]
+mod origins;
+#[cfg(feature = "debug-handles")]
+pub use origins::HandleOrigin;
+#[cfg(feature = "debug-handles")]
+use origins::Origins;
+#[cfg(feature = "egg")]
+mod egg_interop;
+#[cfg(feature = "egg")]
+pub use egg_interop::{to_rec_expr, EggNode};
+#[cfg(feature = "global")]
+pub mod global;
+#[cfg(feature = "serde_json")]
+mod json_value;
+#[cfg(feature = "serde_json")]
+pub use json_value::JsonValue;
+#[cfg(feature = "jsonl")]
+mod jsonl;
+#[cfg(feature = "serde")]
+mod key_serde;
+#[cfg(feature = "memmap2")]
+mod mmap;
+#[cfg(feature = "memmap2")]
+pub use mmap::MappedFile;
+#[cfg(feature = "string_cache")]
+mod string_cache_interop;
+#[cfg(feature = "string-interner")]
+mod string_interner_interop;
+#[cfg(feature = "quickcheck")]
+pub mod testing;
+#[cfg(feature = "uuid")]
+mod uuid_interop;
+
 use std::hash::{Hash, Hasher};
 use std::mem;
-use std::slice;
+use std::ptr;
 use std::borrow::Borrow;
 use std::ops::Deref;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering::{Relaxed, SeqCst};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize};
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use std::sync::{LockResult, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+                TryLockResult};
+use std::time::{Duration, Instant};
+use std::error;
 use std::fmt;
-use std::collections::{hash_map, HashMap};
+use std::hint;
+use std::cmp::{self, Ordering};
+use std::thread;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, BuildHasherDefault};
 
-use crypto::sha1;
+use crypto::sha2;
 use crypto::digest::Digest;
 
 unsafe fn extend_lifetime<'b, T: 'b>(data: &T) -> &'b T {
     mem::transmute(data)
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-struct InternKey {
-    data: [u32; 5],
+/// The digest that identifies an interned value: 160 bits by default, or `N` bytes. `N`
+/// must be a multiple of 4 from 8 to 32. Keys are ordered by their bytes, which gives an
+/// arbitrary but deterministic order for values.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InternKey<const N: usize = 20> {
+    data: [u8; N],
+}
+
+impl<const N: usize> Hash for InternKey<N> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        // The whole key, so that values holding keys are keyed by all of their bits and a
+        // handle hashes the same as its key. `KeyHasher` just folds the bytes together.
+        hasher.write(&self.data);
+    }
+}
+
+/// A `Hasher` that passes `InternKey`s straight through rather than re-hashing them. Keys
+/// are SHA-1 digests, so hashing them again with SipHash only costs time.
+///
+/// Without a salt, keys are predictable and so an attacker can cheaply search for values
+/// whose keys land in the same bucket. Use a salted interner (or a keyed `BuildHasher` such
+/// as `RandomState`) when interning untrusted data.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct KeyHasher {
+    hash: u64,
 }
 
-impl InternKey {
-    pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        let slice = &mut self.data[..];
-        unsafe {
-            let ptr: *mut u8 = mem::transmute(slice.as_ptr());
-            slice::from_raw_parts_mut(ptr, 20)
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // Every bit of a key is as good as any other, so fold them in a word at a time.
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.write_u64(u64::from_le_bytes(word));
         }
     }
 
-    pub fn hash<T: ?Sized + Hash>(data: &T) -> InternKey {
-        let mut hasher = sha1::Sha1::new();
-        hasher.input_hashable(&data);
-        let mut key = InternKey {
-            data: unsafe { mem::uninitialized() },
-        };
-        hasher.result(key.as_slice_mut());
+    fn write_u64(&mut self, i: u64) {
+        self.hash ^= i;
+    }
+}
+
+/// The default `BuildHasher` used by an `Interner`'s internal map.
+pub type BuildKeyHasher = BuildHasherDefault<KeyHasher>;
+
+impl<const N: usize> InternKey<N> {
+    const VALID_WIDTH: () = assert!(N.is_multiple_of(4) && N >= 8 && N <= 32,
+                                    "InternKey's width must be a multiple of 4 from 8 to 32");
+
+    fn zeroed() -> InternKey<N> {
+        let () = Self::VALID_WIDTH;
+        InternKey {
+            data: [0; N],
+        }
+    }
+
+    /// The `i`th 32-bit word of the key.
+    fn word(&self, i: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&self.data[4 * i..4 * i + 4]);
+        u32::from_ne_bytes(word)
+    }
+
+    fn last_word(&self) -> u32 {
+        self.word(N / 4 - 1)
+    }
+
+    /// The digest's bytes.
+    pub fn to_bytes(&self) -> [u8; N] {
+        self.data
+    }
+
+    pub fn from_bytes(bytes: [u8; N]) -> InternKey<N> {
+        let mut key = InternKey::zeroed();
+        key.data = bytes;
         key
     }
+
+    /// Hash `data` with `digest`, keyed or salted with `salt` if one is given.
+    fn hash<T: ?Sized + Hash>(digest: KeyDigest, salt: Option<&Salt>, data: &T) -> InternKey<N> {
+        let mut key = InternKey::zeroed();
+        match digest {
+            KeyDigest::Sha1 if N <= 20 => {
+                let mut hasher = digest::Sha1::new();
+                if let Some(salt) = salt {
+                    hasher.write(&salt.data[..]);
+                }
+                data.hash(&mut hasher);
+                key.data.copy_from_slice(&hasher.finish160()[..N]);
+            },
+            KeyDigest::Sha1 => {
+                // Wider than SHA-1 itself, so use SHA-256 in its place.
+                let mut hasher = sha2::Sha256::new();
+                if let Some(salt) = salt {
+                    hasher.input(&salt.data[..]);
+                }
+                data.hash(&mut digest::DigestWriter(&mut hasher));
+                let mut out = [0; 32];
+                hasher.result(&mut out);
+                key.data.copy_from_slice(&out[..N]);
+            },
+            KeyDigest::Chunked => {
+                // The chunks' digests are digested in turn, as SHA-1 or SHA-256 by width.
+                return InternKey::hash(KeyDigest::Sha1, salt, &digest::chunk_digests(data));
+            },
+            KeyDigest::Fast128 | KeyDigest::Sip128 => {
+                let (c_rounds, d_rounds) = if digest == KeyDigest::Fast128 { (1, 3) } else { (2, 4) };
+                let bits = digest::sip128(salt, data, c_rounds, d_rounds);
+                let n = cmp::min(N, 16);
+                key.data[..n].copy_from_slice(&bits[..n]);
+                // Bytes past the digest's 128 bits include the last word, which picks the
+                // shard, so they can't be left constant.
+                for i in 16..N {
+                    key.data[i] = key.data[i - 12] ^ key.data[i - 4];
+                }
+            },
+        }
+        key
+    }
+}
+
+impl<const N: usize> fmt::Display for InternKey<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..N / 4 {
+            try!(write!(f, "{:x}", self.word(i)));
+        }
+        Ok(())
+    }
 }
 
-impl fmt::Display for InternKey {
+impl<const N: usize> fmt::Debug for InternKey<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:x}{:x}{:x}{:x}{:x}", self.data[0], self.data[1], self.data[2], self.data[3], self.data[4])
+        write!(f, "InternKey({})", self)
+    }
+}
+
+/// A secret mixed into key derivation so that keys can't be predicted (and collisions
+/// can't be targeted) by whoever controls the interned values.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Salt {
+    data: [u8; 16],
+}
+
+impl Salt {
+    pub fn new(data: [u8; 16]) -> Salt {
+        Salt {
+            data: data,
+        }
     }
+
+    /// Generate a salt from the OS-seeded randomness behind `std`'s `RandomState`.
+    pub fn random() -> Salt {
+        let mut data = [0u8; 16];
+        for chunk in data.chunks_mut(8) {
+            let bits = RandomState::new().build_hasher().finish();
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = (bits >> (i * 8)) as u8;
+            }
+        }
+        Salt::new(data)
+    }
+}
+
+impl fmt::Debug for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Salt(..)")
+    }
+}
+
+/// The ways in which the fallible (`try_`) operations can fail.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InternError {
+    /// A thread panicked while holding the interner's lock.
+    Poisoned,
+    /// The interner is full.
+    CapacityExceeded,
+    /// A different value is already interned under the same key.
+    Collision,
+    /// The interner's table doesn't agree with its handles.
+    Inconsistent,
+    /// The entry a `Symbol` named has been removed.
+    Stale,
+    /// A `Symbol` was resolved in a different interner from the one that made it.
+    Foreign,
+    /// A lock couldn't be taken before the deadline.
+    Timeout,
+}
+
+impl fmt::Display for InternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InternError::Poisoned => write!(f, "the interner's lock is poisoned"),
+            InternError::CapacityExceeded => write!(f, "the interner is at capacity"),
+            InternError::Collision => write!(f, "a different value is interned under the same key"),
+            InternError::Inconsistent => write!(f, "the interner's table is inconsistent"),
+            InternError::Stale => write!(f, "the symbol's entry has been removed"),
+            InternError::Foreign => write!(f, "the symbol belongs to a different interner"),
+            InternError::Timeout => write!(f, "timed out waiting for the interner's lock"),
+        }
+    }
+}
+
+impl error::Error for InternError {}
+
+/// Why `Interner::clear` didn't clear the interner.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClearError<const N: usize = 20> {
+    /// These entries, sorted by key, still have handles.
+    Referenced(Vec<InternKey<N>>),
+    /// A lock couldn't be taken.
+    Intern(InternError),
 }
 
-struct InternField<T> {
+impl<const N: usize> From<InternError> for ClearError<N> {
+    fn from(e: InternError) -> ClearError<N> {
+        ClearError::Intern(e)
+    }
+}
+
+impl<const N: usize> fmt::Display for ClearError<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClearError::Referenced(ref keys) => write!(f, "{} entries still have handles", keys.len()),
+            ClearError::Intern(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl<const N: usize> error::Error for ClearError<N> {}
+
+/// An entry in an interner's table: a value, its key and its handle count. These are only
+/// visible to code outside the crate through `MapBackend`.
+pub struct InternField<T, const N: usize = 20> {
+    key: InternKey<N>,
     count: AtomicUsize,
+    // How much of `count` is handles given up by `Interned::pin`, which are never released.
+    pinned: AtomicUsize,
+    // The epoch in which the entry was last inserted or looked up.
+    epoch: AtomicU64,
     data: T,
 }
 
-pub struct Interner<T> {
-    map: Mutex<HashMap<InternKey, InternField<T>>>,
+impl<T, const N: usize> InternField<T, N> {
+    fn new(key: InternKey<N>, data: T) -> InternField<T, N> {
+        InternField {
+            key: key,
+            count: AtomicUsize::new(0),
+            pinned: AtomicUsize::new(0),
+            epoch: AtomicU64::new(0),
+            data: data,
+        }
+    }
+
+    pub fn key(&self) -> &InternKey<N> {
+        &self.key
+    }
+
+    /// Take one off the entry's count, returning what it was. A count that's already zero
+    /// is left alone, so that an inconsistent release can't wrap it around.
+    fn decrement(&self) -> Result<usize, InternError> {
+        self.count
+            .fetch_update(Release, Relaxed, |count| count.checked_sub(1))
+            .map_err(|_| InternError::Inconsistent)
+    }
 }
 
-pub struct Interned<'a, T: 'a> {
-    key: InternKey,
-    interner: &'a Interner<T>,
-    field: &'a InternField<T>,
+/// A lock-free stack of the keys of entries whose count has dropped to zero. Entries are
+/// only removed from the table under the lock, once they've been confirmed to still have
+/// no handles, so the last handle to an entry can be dropped without blocking.
+struct Retired<const N: usize> {
+    head: AtomicPtr<RetiredNode<N>>,
+    // Roughly how many keys are on the stack. It's updated separately from `head`, so it can
+    // be briefly off while keys are being pushed or taken.
+    len: AtomicUsize,
 }
 
-impl<T: Hash> Interner<T> {
-    pub fn new() -> Interner<T> {
-        Interner {
-            map: Mutex::new(HashMap::new()),
+struct RetiredNode<const N: usize> {
+    key: InternKey<N>,
+    next: *mut RetiredNode<N>,
+}
+
+impl<const N: usize> Retired<N> {
+    fn new() -> Retired<N> {
+        Retired {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
         }
     }
 
-    fn intern_with<'a, F>(&'a self, key: InternKey, f: F) -> Interned<'a, T>
-            where F: FnOnce() -> T,
-                  T: 'a
+    fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+
+    fn push(&self, key: InternKey<N>) {
+        let node = Box::into_raw(Box::new(RetiredNode {
+            key: key,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Relaxed);
+        loop {
+            unsafe { (*node).next = head };
+            match self.head.compare_exchange_weak(head, node, Release, Relaxed) {
+                Ok(_) => {
+                    self.len.fetch_add(1, Relaxed);
+                    return;
+                },
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn take_all(&self) -> Vec<InternKey<N>> {
+        let mut keys = Vec::new();
+        let mut node = self.head.swap(ptr::null_mut(), Acquire);
+        self.len.store(0, Relaxed);
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            keys.push(boxed.key);
+        }
+        keys
+    }
+}
+
+impl<const N: usize> Drop for Retired<N> {
+    fn drop(&mut self) {
+        let _ = self.take_all();
+    }
+}
+
+/// How keys are assigned to the shards of an interner's table.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ShardStrategy {
+    /// Use the last 32 bits of the key. The table's default hasher folds these in with the
+    /// rest, so keys sharing a shard still spread evenly over its buckets.
+    #[default]
+    Tail,
+    /// Use the first 32 bits of the key.
+    Head,
+    /// Map the last 32 bits of the key to a shard index, modulo the shard count.
+    Custom(fn(u32) -> usize),
+}
+
+/// What a thread does when a shard's lock is held by another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LockStrategy {
+    /// Block straight away.
+    #[default]
+    Block,
+    /// Retry this many times in a spin loop before blocking.
+    Spin(usize),
+    /// Spin for a number of retries that's tuned by whether spinning has recently been
+    /// enough, then block.
+    Adaptive,
+}
+
+/// What to do when a handle is dropped and the interner's table turns out not to agree with
+/// it (`InternError::Inconsistent`), which means something has corrupted the table. Handles
+/// are dropped in destructors, where a panic during unwinding aborts the process, so while a
+/// thread is already panicking this never panics but logs instead.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum InconsistencyPolicy {
+    /// Panic.
+    Panic,
+    /// Panic in debug builds, and log in release builds, like `debug_assert!`.
+    #[default]
+    DebugAssert,
+    /// Write a message to stderr and carry on.
+    Log,
+    /// Call this function and carry on.
+    Callback(fn(InternError)),
+}
+
+const ADAPTIVE_INITIAL_SPINS: usize = 64;
+const ADAPTIVE_MIN_SPINS: usize = 4;
+const ADAPTIVE_MAX_SPINS: usize = 4096;
+/// How many retired keys build up before an insert stops to reclaim them.
+const RECLAIM_THRESHOLD: usize = 64;
+
+/// The shard count used when none is given: one per core, up to 64.
+pub fn default_shard_count() -> usize {
+    let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    cmp::min(cores.next_power_of_two(), 64)
+}
+
+/// A table of reference-counted, deduplicated values.
+///
+/// The table is split into shards by key, each a map behind its own `RwLock`, the same
+/// layout as a concurrent map like `dashmap`. The maps are `HashMap`s unless `S` is some
+/// other `BuildMap`, such as `DashMapBackend` with the `dashmap` feature. Threads interning
+/// values with keys in different shards never contend, and lookups of values already
+/// interned only take their shard's lock shared. Use `InternerBuilder::shards` to tune the
+/// shard count for a workload, and `Interner::stats` to see how much time is spent waiting
+/// on locks.
+pub struct Interner<T, S: BuildMap<T, N> = BuildKeyHasher, const N: usize = 20> {
+    // Hits only need to bump an entry's atomic count, so they share a shard's lock. Inserts
+    // and removals take it exclusively.
+    shards: Box<[RwLock<S::Map>]>,
+    shard_strategy: ShardStrategy,
+    lock_strategy: LockStrategy,
+    // The current spin count for `LockStrategy::Adaptive`.
+    adaptive_spins: AtomicUsize,
+    retired: Retired<N>,
+    salt: Option<Salt>,
+    // Mixed into keys shown outside the interner.
+    export_salt: Option<Salt>,
+    digest: KeyDigest,
+    counters: Counters,
+    heap_size: Option<fn(&T) -> usize>,
+    stable_ids: Option<Mutex<StableIds<N>>>,
+    symbols: Option<Mutex<Symbols<N>>>,
+    access_stats: Option<AccessTable<N>>,
+    perfect_hash: Option<PerfectHash<N>>,
+    epoch: AtomicU64,
+    // If set, unused entries aren't retired, and only `collect` removes them.
+    mark_and_sweep: bool,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    on_evict: Option<Box<dyn Fn(T, EvictionReason) + Send + Sync>>,
+    eviction_policy: Option<Mutex<Box<dyn EvictionPolicy<N> + Send>>>,
+    inconsistency: InconsistencyPolicy,
+    on_leak: Option<LeakHook<T, N>>,
+    #[cfg(feature = "debug-handles")]
+    origins: Origins<N>,
+    // Unique among the interners created by this process, to brand the symbols it hands out.
+    id: usize,
+}
+
+static NEXT_INTERNER_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_interner_id() -> usize {
+    NEXT_INTERNER_ID.fetch_add(1, Relaxed)
+}
+
+pub struct Interned<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    field: &'a InternField<T, N>,
+    // Identifies the handle among the interner's recorded origins.
+    #[cfg(feature = "debug-handles")]
+    origin: usize,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    fn shard_index(&self, key: &InternKey<N>) -> usize {
+        let mask = self.shards.len() - 1;
+        match self.shard_strategy {
+            ShardStrategy::Tail => key.last_word() as usize & mask,
+            ShardStrategy::Head => key.word(0) as usize & mask,
+            ShardStrategy::Custom(f) => f(key.last_word()) % self.shards.len(),
+        }
+    }
+
+    fn read<'a>(&'a self, key: &InternKey<N>) -> Result<RwLockReadGuard<'a, S::Map>, InternError> {
+        self.read_until(key, None)
+    }
+
+    fn read_until<'a>(&'a self, key: &InternKey<N>, deadline: Option<Instant>)
+            -> Result<RwLockReadGuard<'a, S::Map>, InternError>
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        self.acquire(|| shard.try_read(), || shard.read(), deadline)
+    }
+
+    fn write<'a>(&'a self, key: &InternKey<N>) -> Result<RwLockWriteGuard<'a, S::Map>, InternError> {
+        self.lock_until(key, None)
+    }
+
+    fn lock_until<'a>(&'a self, key: &InternKey<N>, deadline: Option<Instant>)
+            -> Result<RwLockWriteGuard<'a, S::Map>, InternError>
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        self.acquire(|| shard.try_write(), || shard.write(), deadline)
+    }
+
+    fn lock_shard<'a>(&'a self, index: usize) -> Result<RwLockWriteGuard<'a, S::Map>, InternError> {
+        let shard = &self.shards[index];
+        self.acquire(|| shard.try_write(), || shard.write(), None)
+    }
+
+    fn read_shard<'a>(&'a self, index: usize) -> Result<RwLockReadGuard<'a, S::Map>, InternError> {
+        let shard = &self.shards[index];
+        self.acquire(|| shard.try_read(), || shard.read(), None)
+    }
+
+    /// Take a lock according to the interner's `LockStrategy`, recording any wait. With a
+    /// deadline, rather than blocking this keeps retrying until the deadline passes.
+    fn acquire<G, F, B>(&self, try_lock: F, block: B, deadline: Option<Instant>) -> Result<G, InternError>
+            where F: Fn() -> TryLockResult<G>,
+                  B: FnOnce() -> LockResult<G>
     {
-        let mut map = self.map.lock().unwrap();
-        let entry = map.entry(key.clone());
-        let field = match entry {
-            hash_map::Entry::Occupied(oe) => oe.into_mut(),
-            hash_map::Entry::Vacant(ve) => ve.insert(InternField {
-                count: AtomicUsize::new(0),
-                data: f(),
-            }),
+        match try_lock() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(_)) => return Err(InternError::Poisoned),
+            Err(TryLockError::WouldBlock) => (),
+        }
+
+        let start = Instant::now();
+        let spins = match self.lock_strategy {
+            LockStrategy::Block => 0,
+            LockStrategy::Spin(spins) => spins,
+            LockStrategy::Adaptive => self.adaptive_spins.load(Relaxed),
         };
+        for _ in 0..spins {
+            hint::spin_loop();
+            match try_lock() {
+                Ok(guard) => {
+                    self.note_contended(start.elapsed());
+                    self.adapt_spins(true, spins);
+                    return Ok(guard);
+                },
+                Err(TryLockError::Poisoned(_)) => return Err(InternError::Poisoned),
+                Err(TryLockError::WouldBlock) => (),
+            }
+        }
+        self.adapt_spins(false, spins);
+        let guard = match deadline {
+            None => block().map_err(|_| InternError::Poisoned),
+            Some(deadline) => loop {
+                match try_lock() {
+                    Ok(guard) => break Ok(guard),
+                    Err(TryLockError::Poisoned(_)) => break Err(InternError::Poisoned),
+                    Err(TryLockError::WouldBlock) if Instant::now() >= deadline => break Err(InternError::Timeout),
+                    Err(TryLockError::WouldBlock) => thread::yield_now(),
+                }
+            },
+        };
+        self.note_contended(start.elapsed());
+        guard
+    }
+
+    /// Spin longer next time if spinning worked, or back off if it didn't.
+    fn adapt_spins(&self, worked: bool, spins: usize) {
+        if self.lock_strategy == LockStrategy::Adaptive {
+            let spins = if worked {
+                cmp::min(spins + spins / 4 + 1, ADAPTIVE_MAX_SPINS)
+            } else {
+                // Never back off all the way, or spinning could never be found to work again.
+                cmp::max(spins / 2, ADAPTIVE_MIN_SPINS)
+            };
+            self.adaptive_spins.store(spins, Relaxed);
+        }
+    }
+
+    /// Remove the retired entries that still have no handles. The caller should drop the
+    /// returned entries after releasing the lock.
+    fn reclaim_retired(&self) -> Result<Vec<Box<InternField<T, N>>>, InternError> {
+        self.remove_unused(self.retired.take_all())
+    }
+
+    /// Remove the entries under `keys` that have no handles, taking each shard's lock once.
+    fn remove_unused(&self, mut keys: Vec<InternKey<N>>) -> Result<Vec<Box<InternField<T, N>>>, InternError> {
+        keys.sort_by_key(|key| self.shard_index(key));
+        let mut removed = Vec::new();
+        let mut keys = keys.into_iter().peekable();
+        while let Some(index) = keys.peek().map(|key| self.shard_index(key)) {
+            let mut map = try!(self.lock_shard(index));
+            while let Some(key) = keys.peek().cloned() {
+                if self.shard_index(&key) != index {
+                    break;
+                }
+                keys.next();
+                // An entry can be retired more than once, or resurrected by an `intern` after
+                // being retired, so only remove it if it's still there and still unused.
+                let dead = match map.get(&key) {
+                    Some(field) => 0 == field.count.load(Acquire),
+                    None => false,
+                };
+                if dead {
+                    if let Some(field) = map.remove(&key) {
+                        self.removed(&field);
+                        removed.push(field);
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Reclaim retired entries if there are at least `RECLAIM_THRESHOLD` of them.
+    fn maybe_reclaim(&self) {
+        if self.retired.len() >= RECLAIM_THRESHOLD {
+            drop(self.reclaim_retired());
+        }
+    }
+
+    /// Free every entry whose last handle has been dropped. Entries are otherwise reclaimed
+    /// lazily as new values are interned.
+    pub fn reclaim(&self) -> Result<(), InternError> {
+        loop {
+            let removed = try!(self.reclaim_retired());
+            if removed.is_empty() {
+                return Ok(());
+            }
+            // Dropping these may retire the entries of any handles they hold, so go again.
+            drop(removed);
+        }
+    }
+
+    /// Drop many handles at once, removing the entries left without handles straight away.
+    /// Each shard's lock is taken at most once, rather than the entries being retired one
+    /// by one and reclaimed later. Returns the number of entries removed.
+    pub fn release_all<'a, I>(&self, handles: I) -> Result<usize, InternError>
+            where I: IntoIterator<Item=Interned<'a, T, S, N>>,
+                  T: 'a,
+                  S: 'a
+    {
+        let mut dead = Vec::new();
+        let mut result = Ok(());
+        for handle in handles {
+            if !self.owns(&handle) {
+                drop(handle);
+                continue;
+            }
+            #[cfg(feature = "debug-handles")]
+            self.origins.release(handle.origin);
+            match handle.field.decrement() {
+                Err(e) => result = Err(e),
+                Ok(1) if !self.mark_and_sweep => dead.push(handle.field.key.clone()),
+                Ok(_) => (),
+            }
+            mem::forget(handle);
+        }
+        try!(result);
+        let removed = try!(self.remove_unused(dead));
+        Ok(removed.len())
+    }
+
+    /// Remove every entry that has no handles, whether or not it's been retired, returning
+    /// how many were removed. This is the only way entries leave an interner built with
+    /// `InternerBuilder::mark_and_sweep`.
+    pub fn collect(&self) -> Result<usize, InternError> {
+        Ok(try!(self.sweep(|_| true)).len())
+    }
+
+    /// Remove every entry that has no handles and satisfies `pred`. The caller should drop
+    /// the returned entries after releasing the lock.
+    fn sweep<P>(&self, pred: P) -> Result<Vec<Box<InternField<T, N>>>, InternError>
+            where P: Fn(&InternField<T, N>) -> bool
+    {
+        let mut removed = Vec::new();
+        for index in 0..self.shards.len() {
+            let mut map = try!(self.lock_shard(index));
+            let keys: Vec<InternKey<N>> = map.iter()
+                                          .filter(|field| pred(field))
+                                          .filter(|field| 0 == field.count.load(Acquire))
+                                          .map(|field| field.key.clone())
+                                          .collect();
+            for field in keys.iter().filter_map(|key| map.remove(key)) {
+                self.removed(&field);
+                removed.push(field);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove every entry, as long as none of them has handles. Otherwise the interner is
+    /// left as it was and the keys of the entries which still have handles are returned.
+    /// Handles held by other entries' values count too. Handy for isolating test cases which
+    /// share an interner.
+    pub fn clear(&self) -> Result<(), ClearError<N>> {
+        let mut maps = Vec::with_capacity(self.shards.len());
+        for index in 0..self.shards.len() {
+            maps.push(try!(self.lock_shard(index)));
+        }
+        // No handle can be made without a lock or another handle, so with every lock held and
+        // no handles left, none can turn up before the table's empty.
+        let mut referenced: Vec<InternKey<N>> = maps.iter()
+                                                    .flat_map(|map| map.iter())
+                                                    .filter(|field| 0 != field.count.load(Acquire))
+                                                    .map(|field| field.key.clone())
+                                                    .collect();
+        if !referenced.is_empty() {
+            referenced.sort();
+            return Err(ClearError::Referenced(referenced));
+        }
+
+        let mut removed = Vec::new();
+        for map in &mut maps {
+            let keys: Vec<InternKey<N>> = map.iter().map(|field| field.key.clone()).collect();
+            for field in keys.iter().filter_map(|key| map.remove(key)) {
+                self.removed(&field);
+                removed.push(field);
+            }
+        }
+        drop(maps);
+        drop(removed);
+        Ok(())
+    }
+
+    /// Whether `key` is interned and has live handles.
+    fn is_live(&self, key: &InternKey<N>) -> bool {
+        let map = self.read(key).unwrap();
+        map.get(key).is_some_and(|field| field.count.load(Relaxed) > 0)
+    }
+
+    /// The number of entries in the table. This includes entries whose handles have all been
+    /// dropped but which haven't been reclaimed yet.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take a handle to every entry, sorted by key. The order doesn't depend on how the table
+    /// is sharded or on the order values were interned in, so it suits deterministic exports.
+    pub fn handles_by_key<'a>(&'a self) -> Vec<Interned<'a, T, S, N>>
+            where T: 'a
+    {
+        let mut handles = Vec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            // This isn't a lookup, so the entries aren't counted as hits or stamped as used.
+            handles.extend(map.iter().map(|field| {
+                field.count.fetch_add(1, Relaxed);
+                self.wrap(unsafe { extend_lifetime(field) })
+            }));
+        }
+        handles.sort_by(|a, b| a.field.key.cmp(&b.field.key));
+        handles
+    }
+}
+
+impl<T> Interner<T> {
+    pub fn new() -> Interner<T> {
+        Interner::with_hasher(Default::default())
+    }
+
+    /// Create an interner whose keys are derived using `salt`. Use this when interning
+    /// attacker-controlled data to harden against hash-flooding and targeted collisions.
+    pub fn with_salt(salt: Salt) -> Interner<T> {
+        Interner::with_salt_and_hasher(salt, Default::default())
+    }
+
+    /// Create an interner with a freshly generated random salt.
+    pub fn salted() -> Interner<T> {
+        Interner::with_salt(Salt::random())
+    }
+}
+
+impl<T> Interner<T> {
+    /// Create an interner whose table is split into `shards` separately locked shards
+    /// (rounded up to a power of two), with keys assigned to shards by `strategy`.
+    pub fn with_shards(shards: usize, strategy: ShardStrategy) -> Interner<T> {
+        Interner::builder().shards(shards).shard_strategy(strategy).build()
+    }
+}
+
+impl<T, S: BuildHasher> Interner<T, S> {
+    /// Create an interner whose internal map hashes keys using `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Interner<T, S> {
+        Interner::builder().hasher(hash_builder).build()
+    }
+
+    /// Create an interner with both a key salt and a custom `BuildHasher`.
+    pub fn with_salt_and_hasher(salt: Salt, hash_builder: S) -> Interner<T, S> {
+        Interner::builder().salt(salt).hasher(hash_builder).build()
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+
+    fn key_of<B: ?Sized + Hash>(&self, data: &B) -> InternKey<N> {
+        match self.perfect_key(data) {
+            Some(key) => key,
+            None => self.digest_key(data),
+        }
+    }
+
+    fn digest_key<B: ?Sized + Hash>(&self, data: &B) -> InternKey<N> {
+        InternKey::hash(self.digest, self.salt.as_ref(), data)
+    }
+
+    /// Bookkeeping for a new entry, done under its shard's write lock once it's been counted
+    /// by `admit`.
+    fn inserted(&self, field: &InternField<T, N>) {
+        self.note_miss();
+        self.assign_stable_id(&field.key);
+        self.assign_symbol(&field.key);
+        self.policy_insert(&field.key);
+        self.touch(field);
+    }
+
+    /// Report that a handle to `key` was dropped without really being interned, according to
+    /// the interner's `InconsistencyPolicy`.
+    fn inconsistent(&self, key: &InternKey<N>) {
+        let panic = match self.inconsistency {
+            InconsistencyPolicy::Panic => true,
+            InconsistencyPolicy::DebugAssert => cfg!(debug_assertions),
+            InconsistencyPolicy::Log => false,
+            InconsistencyPolicy::Callback(f) => return f(InternError::Inconsistent),
+        };
+        if panic && !thread::panicking() {
+            panic!("The Interned {} was not really interned!", key);
+        }
+        eprintln!("interner: the Interned {} was not really interned", key);
+    }
+
+    /// Bookkeeping for a removed entry, done under its shard's write lock.
+    fn removed(&self, field: &InternField<T, N>) {
+        self.note_removed(field);
+        self.free_symbol(&field.key);
+        self.forget_access(&field.key);
+        self.policy_remove(&field.key);
+    }
+
+    fn handle<'a>(&'a self, field: &InternField<T, N>) -> Interned<'a, T, S, N>
+            where T: 'a
+    {
+        self.note_hit();
+        self.new_handle(field)
+    }
+
+    /// Like `handle`, for an entry that has just been inserted, so it isn't counted as a hit.
+    fn new_handle<'a>(&'a self, field: &InternField<T, N>) -> Interned<'a, T, S, N>
+            where T: 'a
+    {
         field.count.fetch_add(1, Relaxed);
-        let field: &'a InternField<T> = unsafe { extend_lifetime(field) };
+        self.touch(field);
+        self.policy_hit(&field.key);
+        self.record_access(&field.key);
+        let field: &'a InternField<T, N> = unsafe { extend_lifetime(field) };
+        self.wrap(field)
+    }
+
+    /// Make a handle to `field`, whose count has already been bumped for it.
+    fn wrap<'a>(&'a self, field: &'a InternField<T, N>) -> Interned<'a, T, S, N> {
         Interned {
-            key: key,
             interner: self,
             field: field,
+            #[cfg(feature = "debug-handles")]
+            origin: self.origins.record(&field.key),
         }
     }
 
-    pub fn intern<'a>(&'a self, data: T) -> Interned<'a, T>
-            where T: 'a
+    /// Look up `key`, inserting `data` if it's absent. `same` is used to check that a value
+    /// found under `key` really is equal to `data`.
+    fn insert_or_get<'a, E>(&'a self, key: InternKey<N>, data: T, same: E)
+            -> Result<Interned<'a, T, S, N>, InternError>
+            where E: Fn(&T, &T) -> bool,
+                  T: 'a
+    {
+        self.insert_or_get_until(key, data, same, None)
+    }
+
+    /// `insert_or_get`, giving up with `InternError::Timeout` if a lock can't be had before
+    /// `deadline`.
+    fn insert_or_get_until<'a, E>(&'a self, key: InternKey<N>, data: T, same: E, deadline: Option<Instant>)
+            -> Result<Interned<'a, T, S, N>, InternError>
+            where E: Fn(&T, &T) -> bool,
+                  T: 'a
+    {
+        self.note_intern();
+        {
+            let map = try!(self.read_until(&key, deadline));
+            if let Some(field) = map.get(&key) {
+                if !same(&field.data, &data) {
+                    return Err(InternError::Collision);
+                }
+                return Ok(self.handle(field));
+            }
+        }
+
+        let field = Box::new(InternField::new(key, data));
+        self.make_room(self.entry_bytes(&field));
+        let spare;
+        let interned = {
+            let mut map = try!(self.lock_until(&field.key, deadline));
+            if map.get(&field.key).is_none() {
+                try!(self.admit(&field));
+            }
+            let (field, rejected) = map.get_or_insert(field);
+            match rejected {
+                Some(rejected) => {
+                    let collision = !same(&field.data, &rejected.data);
+                    spare = Some(rejected);
+                    if collision {
+                        return Err(InternError::Collision);
+                    }
+                    self.handle(field)
+                },
+                None => {
+                    spare = None;
+                    self.inserted(field);
+                    self.new_handle(field)
+                },
+            }
+        };
+        // Dropping a `T` can drop handles into this interner, so never do it under the lock.
+        drop(spare);
+        // Inserts are when the table grows, so take the opportunity to shrink it too. That
+        // takes more locks, so only do it once enough entries have been retired to be worth
+        // it, and leave it for later if the caller is in a hurry.
+        if deadline.is_none() {
+            self.maybe_reclaim();
+        }
+        Ok(interned)
+    }
+
+    /// Like `insert_or_get`, but only builds the value with `f` if `key` is absent. `matches`
+    /// checks a value found under `key` before `f` has been called.
+    fn try_intern_with<'a, F, M, E>(&'a self, key: InternKey<N>, f: F, matches: M, same: E)
+            -> Result<Interned<'a, T, S, N>, InternError>
+            where F: FnOnce() -> T,
+                  M: Fn(&T) -> bool,
+                  E: Fn(&T, &T) -> bool,
+                  T: 'a
+    {
+        {
+            let map = try!(self.read(&key));
+            if let Some(field) = map.get(&key) {
+                if !matches(&field.data) {
+                    return Err(InternError::Collision);
+                }
+                // Otherwise `insert_or_get` counts the intern.
+                self.note_intern();
+                return Ok(self.handle(field));
+            }
+        }
+
+        // The lock is released while `f` runs so that it can intern into this interner
+        // itself (eg. to hash-cons the children of the node it's building). If the same key
+        // gets inserted in the meantime then the existing value wins and ours is discarded.
+        let data = f();
+        self.insert_or_get(key, data, same)
+    }
+
+    fn intern_with<'a, F>(&'a self, key: InternKey<N>, f: F) -> Interned<'a, T, S, N>
+            where F: FnOnce() -> T,
+                  T: 'a
+    {
+        match self.try_intern_with(key, f, |_| true, |_, _| true) {
+            Ok(interned) => interned,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Intern `data`, returning a handle to the interned copy. Panics if a lock is poisoned
+    /// or the interner is full (see `InternerBuilder::max_entries`). See `try_intern`.
+    pub fn intern<'a>(&'a self, data: T) -> Interned<'a, T, S, N>
+            where T: Hash + 'a
+    {
+        let key = self.key_of(&data);
+        match self.insert_or_get(key, data, |_, _| true) {
+            Ok(interned) => interned,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `intern`, but returns an error rather than panicking. Values already interned
+    /// under the same key are compared against `data` so that key collisions are reported
+    /// rather than silently returning the wrong value.
+    pub fn try_intern<'a>(&'a self, data: T) -> Result<Interned<'a, T, S, N>, InternError>
+            where T: Hash + Eq + 'a
     {
-        let key = InternKey::hash(&data);
-        self.intern_with(key, || data)
+        let key = self.key_of(&data);
+        self.insert_or_get(key, data, |existing, data| existing == data)
     }
 
-    pub fn intern_borrowed<'a, B: ?Sized>(&'a self, data: &B) -> Interned<'a, T>
+    /// Like `try_intern`, but fails with `InternError::Timeout` rather than waiting longer
+    /// than `timeout` for a lock.
+    pub fn try_intern_for<'a>(&'a self, data: T, timeout: Duration) -> Result<Interned<'a, T, S, N>, InternError>
+            where T: Hash + Eq + 'a
+    {
+        let deadline = Instant::now() + timeout;
+        let key = self.key_of(&data);
+        self.insert_or_get_until(key, data, |existing, data| existing == data, Some(deadline))
+    }
+
+    /// Re-intern a value that's already behind a handle. If the handle came from this
+    /// interner this just bumps the reference count, without re-hashing the value or taking
+    /// the lock. Handles from other interners have their value cloned into this one.
+    pub fn intern_handle<'a, 'b>(&'a self, handle: &Interned<'b, T, S, N>) -> Interned<'a, T, S, N>
+            where T: Hash + Clone + 'a
+    {
+        if self.owns(handle) {
+            handle.field.count.fetch_add(1, Relaxed);
+            let field: &'a InternField<T, N> = unsafe { extend_lifetime(handle.field) };
+            self.wrap(field)
+        } else {
+            self.intern_borrowed_with(&**handle, |data| data.clone())
+        }
+    }
+
+    /// Whether `handle` came from this interner. Handles from different interners compare
+    /// equal if their keys are, so check this where mixing them up would be a mistake.
+    pub fn owns(&self, handle: &Interned<T, S, N>) -> bool {
+        ptr::eq(handle.interner, self)
+    }
+
+    pub fn intern_borrowed<'a, B: ?Sized>(&'a self, data: &B) -> Interned<'a, T, S, N>
             where B: Hash + ToOwned<Owned=T>,
                   T: Hash + Borrow<B> + 'a
     {
-        let key = InternKey::hash(data);
-        self.intern_with(key, || data.to_owned())
+        self.intern_borrowed_with(data, |data| data.to_owned())
+    }
+
+    /// Like `intern_borrowed`, but returns an error rather than panicking. See `try_intern`.
+    pub fn try_intern_borrowed<'a, B>(&'a self, data: &B) -> Result<Interned<'a, T, S, N>, InternError>
+            where B: ?Sized + Hash + Eq + ToOwned<Owned=T>,
+                  T: Hash + Borrow<B> + 'a
+    {
+        let key = self.key_of(data);
+        self.try_intern_with(key,
+                             || data.to_owned(),
+                             |existing| existing.borrow() == data,
+                             |existing, owned| existing.borrow() == owned.borrow())
+    }
+
+    /// Intern the value that `f` builds from `data`. The key is derived from `data` alone, so
+    /// `f` must build equal values from equal inputs, and it's only called if nothing is
+    /// interned under that key yet. It runs without any locks held, so it's free to intern
+    /// into this interner itself.
+    pub fn intern_borrowed_with<'a, B, F>(&'a self, data: &B, f: F) -> Interned<'a, T, S, N>
+            where B: ?Sized + Hash,
+                  F: FnOnce(&B) -> T,
+                  T: 'a
+    {
+        let key = self.key_of(data);
+        self.intern_with(key, || f(data))
+    }
+
+    /// Add `data` to the table without taking a handle to it, returning whether it was new.
+    /// Like entries moved in by `drain_into`, it stays until it's reclaimed after being
+    /// interned and released again.
+    fn insert_unreferenced(&self, data: T) -> Result<bool, InternError>
+            where T: Hash
+    {
+        let field = Box::new(InternField::new(self.key_of(&data), data));
+        let spare = {
+            let mut map = try!(self.write(&field.key));
+            if map.get(&field.key).is_none() {
+                try!(self.admit(&field));
+            }
+            let (field, rejected) = map.get_or_insert(field);
+            if rejected.is_none() {
+                self.inserted(field);
+            }
+            rejected
+        };
+        Ok(spare.is_none())
+    }
+
+    /// Move every entry that has no live handles into `other`, returning how many were moved.
+    /// Entries keep their keys if both interners use the same salt and digest, and are re-keyed
+    /// otherwise. Where `other` already has an entry for a key, its entry is kept, and entries
+    /// that don't fit under `other`'s limits are dropped. Entries that
+    /// have already been reclaimed are gone, so drop handles after the last intern if their
+    /// entries should be moved.
+    pub fn drain_into<S2: BuildMap<T, N>>(&self, other: &Interner<T, S2, N>) -> Result<usize, InternError>
+            where T: Hash
+    {
+        if ptr::eq(self as *const _ as *const u8, other as *const _ as *const u8) {
+            return Ok(0);
+        }
+
+        // Never hold locks on both interners at once, so that draining in both directions
+        // can't deadlock.
+        let mut drained: Vec<Box<InternField<T, N>>> = Vec::new();
+        for index in 0..self.shards.len() {
+            let mut map = try!(self.lock_shard(index));
+            let keys: Vec<InternKey<N>> = map.iter()
+                                          .filter(|field| 0 == field.count.load(Acquire))
+                                          .map(|field| field.key.clone())
+                                          .collect();
+            for field in keys.iter().filter_map(|key| map.remove(key)) {
+                self.removed(&field);
+                drained.push(field);
+            }
+        }
+
+        let rekey = self.salt != other.salt || self.digest != other.digest;
+        let mut moved = 0;
+        let mut spare = Vec::new();
+        for mut field in drained {
+            if rekey {
+                field.key = other.key_of(&field.data);
+            }
+            let mut map = try!(other.write(&field.key));
+            if map.get(&field.key).is_none() && other.admit(&field).is_err() {
+                spare.push(field);
+                continue;
+            }
+            match map.get_or_insert(field) {
+                (_, Some(rejected)) => spare.push(rejected),
+                (field, None) => {
+                    other.inserted(field);
+                    moved += 1;
+                },
+            }
+        }
+        drop(spare);
+        Ok(moved)
+    }
+
+    /// Get a handle to the value equal to `data` if one is already interned.
+    pub fn get<'a, B: ?Sized + Hash>(&'a self, data: &B) -> Option<Interned<'a, T, S, N>>
+            where T: Borrow<B> + 'a
+    {
+        let key = self.key_of(data);
+        let map = self.read(&key).unwrap();
+        map.get(&key).map(|field| self.handle(field))
+    }
+
+    /// Intern `data` under the key derived from whatever `key` extracts from it. This allows
+    /// interning values that don't implement `Hash` themselves, such as trait objects, as
+    /// long as `key` identifies them. Values with equal keys are treated as equal.
+    pub fn intern_by<'a, K, F>(&'a self, data: T, key: F) -> Interned<'a, T, S, N>
+            where K: Hash,
+                  F: FnOnce(&T) -> K,
+                  T: 'a
+    {
+        let key = self.key_of(&key(&data));
+        match self.insert_or_get(key, data, |_, _| true) {
+            Ok(interned) => interned,
+            Err(e) => panic!("{}", e),
+        }
     }
 }
 
-impl<'a, T> Deref for Interned<'a, T> {
+/// Lets values be interned from the value's side, ie. `value.intern_in(&interner)`.
+/// Borrowed strings and slices intern into interners of their owned forms.
+pub trait Intern<T> {
+    fn intern_in<'a, S: BuildMap<T, N>, const N: usize>(self, interner: &'a Interner<T, S, N>) -> Interned<'a, T, S, N>
+            where T: 'a;
+}
+
+impl<T: Hash> Intern<T> for T {
+    fn intern_in<'a, S: BuildMap<T, N>, const N: usize>(self, interner: &'a Interner<T, S, N>) -> Interned<'a, T, S, N>
+            where T: 'a
+    {
+        interner.intern(self)
+    }
+}
+
+impl Intern<String> for &str {
+    fn intern_in<'a, S: BuildMap<String, N>, const N: usize>(self, interner: &'a Interner<String, S, N>) -> Interned<'a, String, S, N>
+            where String: 'a
+    {
+        interner.intern_borrowed(self)
+    }
+}
+
+impl<T: Hash + Clone> Intern<Vec<T>> for &[T] {
+    fn intern_in<'a, S: BuildMap<Vec<T>, N>, const N: usize>(self, interner: &'a Interner<Vec<T>, S, N>) -> Interned<'a, Vec<T>, S, N>
+            where T: 'a
+    {
+        interner.intern_borrowed(self)
+    }
+}
+
+impl<'a, T: Deref, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// Get at the value behind a pointer-like interned value, eg. the `dyn Trait` inside an
+    /// `Interned<Box<dyn Trait>>`.
+    pub fn as_deref(&self) -> &T::Target {
+        &self.field.data
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// The key the value is interned under.
+    pub fn key(&self) -> &InternKey<N> {
+        &self.field.key
+    }
+
+    /// An owned copy of the value, for handing to code that can't take a handle.
+    pub fn cloned(&self) -> T
+            where T: Clone
+    {
+        self.field.data.clone()
+    }
+
+    /// An owned copy of what the value points to, eg. a `String` from an `Arc<str>` or a
+    /// `Vec<u8>` from a `Box<[u8]>`.
+    pub fn to_owned_value(&self) -> <T::Target as ToOwned>::Owned
+            where T: Deref,
+                  T::Target: ToOwned
+    {
+        (*self.field.data).to_owned()
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Deref for Interned<'a, T, S, N> {
     type Target = T;
 
     fn deref<'b>(&'b self) -> &'b T {
@@ -117,56 +1371,330 @@ impl<'a, T> Deref for Interned<'a, T> {
     }
 }
 
-impl<'a, T> Drop for Interned<'a, T> {
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// Drop the handle's reference, retiring the entry if this was the last one and the
+    /// interner isn't in mark-and-sweep mode.
+    fn release(&self) -> Result<(), InternError> {
+        // Counts only go up from zero under the lock, and retired entries are only removed
+        // under the lock once their count is confirmed to still be zero, so there's no need
+        // to take the lock here.
+        #[cfg(feature = "debug-handles")]
+        self.interner.origins.release(self.origin);
+        if 1 == try!(self.field.decrement()) && !self.interner.mark_and_sweep {
+            self.interner.retired.push(self.field.key.clone());
+        }
+        Ok(())
+    }
+
+    /// Drop this handle, returning an error instead of panicking if the interner's table
+    /// turns out to be inconsistent.
+    pub fn try_release(self) -> Result<(), InternError> {
+        let result = self.release();
+        mem::forget(self);
+        result
+    }
+}
+
+// These are the bounds the compiler would infer, spelled out so they can't silently change.
+//
+// Moving an interner to another thread moves its values, so it's `Send` when they are. A
+// shared interner hands out `&T` to any thread that interns into it, and the last handle to
+// drop may leave the value to be dropped by whichever thread reclaims it, so it's `Sync`
+// only when values are both `Send` and `Sync`. Counts are atomic and the tables are behind
+// locks, so nothing else is shared unsynchronized. The shards' maps are used from any
+// thread that takes their lock.
+unsafe impl<T: Send, S: BuildMap<T, N>, const N: usize> Send for Interner<T, S, N> where S::Map: Send {}
+unsafe impl<T: Send + Sync, S: BuildMap<T, N>, const N: usize> Sync for Interner<T, S, N> where S::Map: Send + Sync {}
+
+// A handle is a shared reference to its interner and entry, so it can go wherever a
+// `&Interner` can.
+unsafe impl<'a, T: Send + Sync, S: BuildMap<T, N>, const N: usize> Send for Interned<'a, T, S, N> where S::Map: Send + Sync {}
+unsafe impl<'a, T: Send + Sync, S: BuildMap<T, N>, const N: usize> Sync for Interned<'a, T, S, N> where S::Map: Send + Sync {}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Drop for Interned<'a, T, S, N> {
     fn drop<'b>(&'b mut self) {
-        if 1 == self.field.count.fetch_sub(1, Relaxed) {
-            let mut map = self.interner.map.lock().unwrap();
-            let entry = map.entry(self.key.clone());
-            match entry {
-                hash_map::Entry::Occupied(oe) => {
-                    if 0 == oe.get().count.load(SeqCst) {
-                        let _ = oe.remove();
-                    }
-                }
-                hash_map::Entry::Vacant(_) => panic!("The Interned was not really interned!"),
-            }
+        if let Err(InternError::Inconsistent) = self.release() {
+            self.interner.inconsistent(&self.field.key);
         }
     }
 }
 
-impl<'a, T> Hash for Interned<'a, T> {
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Hash for Interned<'a, T, S, N> {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
-        self.key.hash(hasher);
+        // The same as the key's, as `Borrow<InternKey>` requires.
+        self.field.key.hash(hasher)
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Borrow<InternKey<N>> for Interned<'a, T, S, N> {
+    fn borrow(&self) -> &InternKey<N> {
+        &self.field.key
     }
 }
 
-impl<'a, T> PartialEq for Interned<'a, T> {
-    fn eq(&self, other: &Interned<'a, T>) -> bool {
-        self.key == other.key
+impl<'a, T, S: BuildMap<T, N>, const N: usize> PartialEq for Interned<'a, T, S, N> {
+    fn eq(&self, other: &Interned<'a, T, S, N>) -> bool {
+        self.field.key == other.field.key
     }
 }
 
-impl<'a, T> Clone for Interned<'a, T> {
-    fn clone(&self) -> Interned<'a, T> {
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Eq for Interned<'a, T, S, N> {}
+
+// Comparisons with plain values go by value, since a plain value has no key.
+impl<'a, T: PartialEq, S: BuildMap<T, N>, const N: usize> PartialEq<T> for Interned<'a, T, S, N> {
+    fn eq(&self, other: &T) -> bool {
+        self.field.data == *other
+    }
+}
+
+impl<'a, T: PartialOrd, S: BuildMap<T, N>, const N: usize> PartialOrd<T> for Interned<'a, T, S, N> {
+    fn partial_cmp(&self, other: &T) -> Option<Ordering> {
+        self.field.data.partial_cmp(other)
+    }
+}
+
+impl<'a, S: BuildMap<String, N>, const N: usize> PartialEq<str> for Interned<'a, String, S, N> {
+    fn eq(&self, other: &str) -> bool {
+        self.field.data == other
+    }
+}
+
+impl<'a, S: BuildMap<String, N>, const N: usize> PartialOrd<str> for Interned<'a, String, S, N> {
+    fn partial_cmp(&self, other: &str) -> Option<Ordering> {
+        self.field.data[..].partial_cmp(other)
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Clone for Interned<'a, T, S, N> {
+    fn clone(&self) -> Interned<'a, T, S, N> {
         self.field.count.fetch_add(1, Relaxed);
-        Interned {
-            key: self.key.clone(),
-            interner: self.interner,
-            field: self.field,
-        }
+        self.interner.wrap(self.field)
     }
 }
 
-impl<'a, T: fmt::Debug> fmt::Debug for Interned<'a, T> {
+impl<'a, T: fmt::Debug, S: BuildMap<T, N>, const N: usize> fmt::Debug for Interned<'a, T, S, N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f, "Interned[{}] ", self.key));
+        try!(write!(f, "Interned[{}] ", self.exported_key()));
         self.field.data.fmt(f)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Interner;
+    use std::collections::hash_map::{DefaultHasher, RandomState};
+    use std::sync::Arc;
+    use std::thread;
+
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::time::Duration;
+
+    use super::{ClearError, InconsistencyPolicy, Intern, InternKey, Interner, Interned, InternError, KeyDigest,
+                LockStrategy, ShardStrategy};
+
+    #[test]
+    fn send_sync() {
+        fn send<X: Send>() {}
+        fn sync<X: Sync>() {}
+        send::<Interner<String>>();
+        sync::<Interner<String>>();
+        send::<Interned<'static, String>>();
+        sync::<Interned<'static, String>>();
+        send::<Interner<::std::cell::Cell<u8>>>();
+        send::<Interner<String, RandomState>>();
+        sync::<Interner<String, RandomState>>();
+    }
+
+    #[test]
+    fn adaptive_spinning() {
+        let interner = Interner::builder().shards(1).lock_strategy(LockStrategy::Adaptive).build();
+        let a = interner.intern(String::from("a"));
+        thread::scope(|scope| {
+            let map = interner.lock_shard(0).unwrap();
+            let reader = scope.spawn(|| interner.get("a"));
+            thread::sleep(Duration::from_millis(50));
+            drop(map);
+            assert_eq!(reader.join().unwrap(), Some(a.clone()));
+        });
+        // Spinning wasn't enough, so it backs off.
+        assert_eq!(interner.adaptive_spins.load(Relaxed), super::ADAPTIVE_INITIAL_SPINS / 2);
+
+        // However long contention goes on, it keeps spinning a little...
+        for _ in 0..64 {
+            let spins = interner.adaptive_spins.load(Relaxed);
+            interner.adapt_spins(false, spins);
+        }
+        assert_eq!(interner.adaptive_spins.load(Relaxed), super::ADAPTIVE_MIN_SPINS);
+        // ...so that it notices once spinning works again, and recovers.
+        for _ in 0..64 {
+            let spins = interner.adaptive_spins.load(Relaxed);
+            interner.adapt_spins(true, spins);
+        }
+        assert_eq!(interner.adaptive_spins.load(Relaxed), super::ADAPTIVE_MAX_SPINS);
+    }
+
+    #[test]
+    fn intern_timeout() {
+        let interner = Interner::builder().shards(1).build();
+        let a = interner.intern(String::from("a"));
+        thread::scope(|scope| {
+            let map = interner.lock_shard(0).unwrap();
+            let b = scope.spawn(|| interner.try_intern_for(String::from("b"), Duration::from_millis(10)).err());
+            assert_eq!(b.join().unwrap(), Some(InternError::Timeout));
+            drop(map);
+        });
+        assert_eq!(interner.try_intern_for(String::from("a"), Duration::from_millis(10)).unwrap(), a);
+    }
+
+    #[test]
+    fn handles_by_key() {
+        let sharded = Interner::builder().shards(8).build();
+        let single = Interner::builder().shards(1).build();
+        let mut held = Vec::new();
+        for i in 0..50 {
+            held.push(sharded.intern(i.to_string()));
+            held.push(single.intern((49 - i).to_string()));
+        }
+        let sharded: Vec<String> = sharded.handles_by_key().iter().map(|h| (**h).clone()).collect();
+        let single: Vec<String> = single.handles_by_key().iter().map(|h| (**h).clone()).collect();
+        assert_eq!(sharded.len(), 50);
+        assert_eq!(sharded, single);
+
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        interner.advance_epoch();
+        drop(interner.handles_by_key());
+        assert!(!a.is_current());
+        drop(a);
+        assert_eq!(interner.sweep_stale().unwrap(), 1);
+    }
+
+    #[test]
+    fn max_entries() {
+        let interner = Interner::builder().shards(4).max_entries(2).build();
+        let a = interner.try_intern(String::from("a")).unwrap();
+        let b = interner.try_intern(String::from("b")).unwrap();
+        assert_eq!(interner.try_intern(String::from("c")).err(), Some(InternError::CapacityExceeded));
+        assert_eq!(interner.try_intern(String::from("a")).unwrap(), a);
+        drop(b);
+        let c = interner.try_intern(String::from("c")).unwrap();
+        assert_eq!(&c[..], "c");
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.stats().peak_entries, 2);
+    }
+
+    #[test]
+    fn max_bytes() {
+        let entry = Interner::<Vec<u8>>::new().entry_bytes(&super::InternField::new(InternKey::zeroed(), Vec::new()));
+        let interner = Interner::builder()
+                                .heap_size(|v: &Vec<u8>| v.len())
+                                .max_bytes(3 * entry + 1000)
+                                .build();
+        let small = interner.try_intern(vec![0; 10]).unwrap();
+        let big = interner.try_intern(vec![1; 900]).unwrap();
+        assert_eq!(interner.try_intern(vec![2; 100]).err(), Some(InternError::CapacityExceeded));
+        let medium = interner.try_intern(vec![3; 50]).unwrap();
+        drop(big);
+        let large = interner.try_intern(vec![2; 100]).unwrap();
+        assert_eq!(interner.len(), 3);
+        assert_eq!(small.len() + medium.len() + large.len(), 160);
+        assert!(interner.stats().bytes <= 3 * entry + 1000);
+    }
+
+    #[test]
+    fn clear() {
+        let interner = Interner::builder().shards(4).mark_and_sweep().build();
+        let a = interner.intern(String::from("a"));
+        drop(interner.intern(String::from("b")));
+        assert_eq!(interner.clear(), Err(ClearError::Referenced(vec![a.key().clone()])));
+        assert_eq!(interner.len(), 2);
+        drop(a);
+        assert_eq!(interner.clear(), Ok(()));
+        assert!(interner.is_empty());
+        assert_eq!(&interner.intern(String::from("a"))[..], "a");
+    }
+
+    #[test]
+    fn inconsistency_policy() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static REPORTED: AtomicUsize = AtomicUsize::new(0);
+        fn report(e: InternError) {
+            assert_eq!(e, InternError::Inconsistent);
+            REPORTED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let interner = Interner::builder()
+                                .mark_and_sweep()
+                                .on_inconsistency(InconsistencyPolicy::Callback(report))
+                                .build();
+        let a = interner.intern(String::from("a"));
+        let field = a.field;
+        a.field.count.fetch_sub(1, Relaxed);
+        drop(a);
+        assert_eq!(REPORTED.load(Ordering::Relaxed), 1);
+        // The count doesn't wrap around below zero.
+        assert_eq!(field.count.load(Relaxed), 0);
+
+        let interner = Interner::builder().on_inconsistency(InconsistencyPolicy::Log).mark_and_sweep().build();
+        let a = interner.intern(String::from("a"));
+        a.field.count.fetch_sub(1, Relaxed);
+        drop(a);
+    }
+
+    #[test]
+    fn release_all() {
+        let interner = Interner::new();
+        let keep = interner.intern(String::from("keep"));
+        let handles: Vec<_> = (0..100).map(|i| interner.intern(i.to_string()))
+                                      .chain(vec![keep.clone(), keep.clone()])
+                                      .collect();
+        assert_eq!(interner.release_all(handles).unwrap(), 100);
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.get("keep"), Some(keep));
+    }
+
+    #[test]
+    fn owned_copies() {
+        let strings = Interner::new();
+        let a = strings.intern(String::from("a"));
+        let copy: String = a.cloned();
+        assert_eq!(copy, "a");
+
+        let arcs = Interner::new();
+        let b = arcs.intern(Arc::<str>::from("b"));
+        let copy: String = b.to_owned_value();
+        assert_eq!(copy, "b");
+    }
+
+    #[test]
+    fn compare_with_values() {
+        let interner = Interner::new();
+        let words: Vec<_> = ["pear", "apple", "fig"].iter().map(|w| interner.intern_borrowed(*w)).collect();
+        let early: Vec<&str> = words.iter().filter(|w| **w < *"c").map(|w| &w[..]).collect();
+        assert_eq!(early, ["apple"]);
+        assert!(words[0] == *"pear");
+        let fig = String::from("fig");
+        assert!(words[2] == fig);
+        assert!(words[2] >= fig);
+
+        let numbers = Interner::new();
+        assert!(numbers.intern(3) > 2);
+    }
+
+    #[test]
+    fn mark_and_sweep() {
+        let interner = Interner::builder().mark_and_sweep().build();
+        let a = interner.intern(String::from("a"));
+        drop(interner.intern(String::from("b")));
+        drop(interner.intern(String::from("c")));
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.collect().unwrap(), 2);
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.get("a"), Some(a));
+    }
 
     #[derive(Hash)]
     enum Foo<'i> {
@@ -190,5 +1718,277 @@ mod tests {
         assert_eq!(*s0, "hello");
         assert_eq!(*s0, *s1);
     }
-}
 
+    #[test]
+    fn salted_keys() {
+        let a = Interner::salted();
+        let b = Interner::salted();
+        let a0 = a.intern(String::from("hello"));
+        let a1 = a.intern_borrowed("hello");
+        let b0 = b.intern(String::from("hello"));
+        assert_eq!(a0, a1);
+        assert!(a0.field.key != b0.field.key);
+        assert_eq!(*a0, *b0);
+    }
+
+    #[test]
+    fn custom_hasher() {
+        let interner = Interner::with_hasher(RandomState::new());
+        let s0 = interner.intern(String::from("hello"));
+        let s1 = interner.intern_borrowed("hello");
+        assert_eq!(s0, s1);
+        assert_eq!(interner.len(), 1);
+
+        // Every shard hashes with the same hasher, so it needn't be `Clone`.
+        struct Unclonable(RandomState);
+        impl BuildHasher for Unclonable {
+            type Hasher = DefaultHasher;
+            fn build_hasher(&self) -> DefaultHasher {
+                self.0.build_hasher()
+            }
+        }
+        let interner = Interner::with_hasher(Unclonable(RandomState::new()));
+        assert_eq!(interner.intern(String::from("hello")), interner.intern_borrowed("hello"));
+    }
+
+    #[test]
+    fn intern_handle() {
+        let a = Interner::new();
+        let b = Interner::new();
+        let s0 = a.intern(String::from("hello"));
+        let s1 = a.intern_handle(&s0);
+        assert_eq!(s0.field.count.load(super::Relaxed), 2);
+        let s2 = b.intern_handle(&s0);
+        assert_eq!(*s2, "hello");
+        assert!(!::std::ptr::eq(s1.field, s2.field));
+        assert_eq!(b.len(), 1);
+    }
+
+    #[test]
+    fn deferred_reclamation() {
+        let interner = Interner::new();
+        let a = interner.intern(1u32);
+        drop(a);
+        assert_eq!(interner.len(), 1);
+        let b = interner.intern(1u32);
+        drop(b);
+        // One retired entry isn't worth holding up an insert for...
+        let c = interner.intern(2u32);
+        assert_eq!(interner.len(), 2);
+        // ...but a batch of them is.
+        for i in 0..super::RECLAIM_THRESHOLD as u32 {
+            drop(interner.intern(i + 3));
+        }
+        assert!(interner.len() < super::RECLAIM_THRESHOLD);
+        assert!(interner.get(&1u32).is_none());
+        drop(c);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 0);
+    }
+
+    #[test]
+    fn intern_in() {
+        let strings = Interner::new();
+        let s0 = String::from("hello").intern_in(&strings);
+        let s1 = "hello".intern_in(&strings);
+        assert_eq!(s0, s1);
+
+        let vecs = Interner::new();
+        let v0 = vec![1u8, 2, 3].intern_in(&vecs);
+        let v1 = (&[1u8, 2, 3][..]).intern_in(&vecs);
+        assert_eq!(v0, v1);
+    }
+
+    trait Shape {
+        fn name(&self) -> String;
+    }
+
+    struct Square(u32);
+
+    impl Shape for Square {
+        fn name(&self) -> String {
+            format!("square {}", self.0)
+        }
+    }
+
+    struct Circle(u32);
+
+    impl Shape for Circle {
+        fn name(&self) -> String {
+            format!("circle {}", self.0)
+        }
+    }
+
+    #[test]
+    fn trait_objects() {
+        let interner: Interner<Box<dyn Shape>> = Interner::new();
+        let a = interner.intern_by(Box::new(Square(1)), |s| s.name());
+        let b = interner.intern_by(Box::new(Circle(1)), |s| s.name());
+        let c = interner.intern_by(Box::new(Square(1)), |s| s.name());
+        assert!(a == c);
+        assert!(a != b);
+        let shape: &dyn Shape = b.as_deref();
+        assert_eq!(shape.name(), "circle 1");
+    }
+
+    #[test]
+    fn drain_into() {
+        let task = Interner::new();
+        let global = Interner::salted();
+        let kept = task.intern(String::from("kept"));
+        let moved = task.intern(String::from("moved"));
+        let both = task.intern(String::from("both"));
+        let global_both = global.intern(String::from("both"));
+        drop(moved);
+        drop(both);
+
+        assert_eq!(task.drain_into(&global), Ok(1));
+        assert_eq!(task.len(), 1);
+        assert_eq!(global.len(), 2);
+        assert_eq!(*global.get("moved").unwrap(), "moved");
+        assert_eq!(*kept, "kept");
+        assert_eq!(*global_both, "both");
+    }
+
+    #[test]
+    fn shards() {
+        fn by_low_bits(bits: u32) -> usize {
+            bits as usize
+        }
+
+        let interners = vec![
+            Interner::with_shards(1, ShardStrategy::Tail),
+            Interner::with_shards(3, ShardStrategy::Head),
+            Interner::with_shards(16, ShardStrategy::Custom(by_low_bits)),
+        ];
+        for interner in &interners {
+            let handles: Vec<_> = (0..100u32).map(|i| interner.intern(i)).collect();
+            assert_eq!(interner.len(), 100);
+            assert_eq!(*interner.get(&42).unwrap(), 42);
+            drop(handles);
+            interner.reclaim().unwrap();
+            assert!(interner.is_empty());
+        }
+        assert_eq!(interners[1].shards.len(), 4);
+    }
+
+    #[test]
+    fn key_bytes() {
+        let interner = Interner::new();
+        let a = interner.intern(7u32);
+        let bytes = a.key().to_bytes();
+        assert_eq!(&InternKey::from_bytes(bytes), a.key());
+    }
+
+    #[test]
+    fn key_widths() {
+        let interner = Interner::new();
+        let narrow = Interner::builder_with_key_bytes::<8>().build();
+        let wide = Interner::builder_with_key_bytes::<32>().build();
+        let a = interner.intern(String::from("a"));
+        let n = narrow.intern(String::from("a"));
+        let w = wide.intern(String::from("a"));
+        assert_eq!(narrow.intern_borrowed("a"), n);
+        assert_eq!(wide.intern_borrowed("a"), w);
+        assert_eq!(n.key().to_bytes()[..], a.key().to_bytes()[..8]);
+        assert!(narrow.intern(String::from("b")).key() != n.key());
+
+        let fast = Interner::builder_with_key_bytes::<24>().key_digest(KeyDigest::Fast128).build();
+        let f = fast.intern(String::from("a"));
+        assert_eq!(fast.intern_borrowed("a"), f);
+        assert_eq!(InternKey::<24>::from_bytes(f.key().to_bytes()), *f.key());
+    }
+
+    #[derive(PartialEq, Eq, Debug)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, _: &mut H) {}
+    }
+
+    #[test]
+    fn try_intern() {
+        let interner = Interner::new();
+        let a0 = interner.try_intern(Colliding(0)).unwrap();
+        let a1 = interner.try_intern(Colliding(0)).unwrap();
+        assert_eq!(a0, a1);
+        assert_eq!(interner.try_intern(Colliding(1)), Err(InternError::Collision));
+        assert_eq!(a0.try_release(), Ok(()));
+        assert_eq!(a1.try_release(), Ok(()));
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 0);
+
+        let strings: Interner<String> = Interner::new();
+        assert_eq!(*strings.try_intern_borrowed("hello").unwrap(), "hello");
+    }
+
+    #[derive(Hash)]
+    enum Tree<'i> {
+        Leaf(u32),
+        Node(Interned<'i, Tree<'i>>, Interned<'i, Tree<'i>>),
+    }
+
+    #[test]
+    fn reentrant_factory() {
+        let interner: &'static Interner<Tree<'static>> = Box::leak(Box::new(Interner::new()));
+        let node = interner.intern_borrowed_with(&(1u32, 2u32), |&(l, r)| {
+            Tree::Node(interner.intern(Tree::Leaf(l)), interner.intern(Tree::Leaf(r)))
+        });
+        match *node {
+            Tree::Node(ref l, ref r) => match (&**l, &**r) {
+                (&Tree::Leaf(1), &Tree::Leaf(2)) => (),
+                _ => panic!("wrong children"),
+            },
+            Tree::Leaf(..) => panic!("expected a node"),
+        }
+        assert_eq!(interner.len(), 3);
+        drop(node);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 0);
+    }
+
+    #[test]
+    fn concurrent_drop_and_resurrect() {
+        let interner = Arc::new(Interner::new());
+        let threads: Vec<_> = (0..8).map(|t| {
+            let interner = interner.clone();
+            thread::spawn(move || {
+                for i in 0..10000u32 {
+                    let a = interner.intern((i + t) % 4);
+                    let b = a.clone();
+                    drop(a);
+                    let c = interner.intern(i % 4);
+                    drop(b);
+                    drop(c);
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 0);
+    }
+
+    #[test]
+    fn concurrent_shared_handles() {
+        let interner = Arc::new(Interner::new());
+        let held = interner.intern(String::from("held"));
+        let threads: Vec<_> = (0..8).map(|_| {
+            let interner = interner.clone();
+            thread::spawn(move || {
+                for _ in 0..10000 {
+                    let s = interner.intern_borrowed("held");
+                    assert_eq!(*s, "held");
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(held.field.count.load(super::Relaxed), 1);
+        drop(held);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 0);
+    }
+}
@@ -0,0 +1,401 @@
+//! A single-threaded interner, which needs no locks or atomic counts.
+
+use std::borrow::Borrow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use {extend_lifetime, BuildKeyHasher, InternError, InternKey, InternerApi, KeyDigest, Salt};
+
+struct LocalField<T, const N: usize> {
+    key: InternKey<N>,
+    count: Cell<usize>,
+    data: T,
+}
+
+/// Like `Interner`, but for use from one thread only. Its table is a `RefCell` and its
+/// counts are `Cell`s, so interning and dropping handles cost no synchronization. Handles
+/// are `LocalInterned`s, and neither they nor the interner can be shared between threads.
+pub struct LocalInterner<T, const N: usize = 20> {
+    map: RefCell<HashMap<InternKey<N>, Box<LocalField<T, N>>, BuildKeyHasher>>,
+    // Keys of entries whose last handle has been dropped, to be removed on the next insert.
+    retired: RefCell<Vec<InternKey<N>>>,
+    salt: Option<Salt>,
+    digest: KeyDigest,
+}
+
+pub struct LocalInterned<'a, T: 'a, const N: usize = 20> {
+    interner: &'a LocalInterner<T, N>,
+    field: &'a LocalField<T, N>,
+}
+
+impl<T> LocalInterner<T> {
+    pub fn new() -> LocalInterner<T> {
+        LocalInterner::with_options(None, KeyDigest::Sha1)
+    }
+
+    /// Create an interner whose keys are derived using `salt`. See `Interner::with_salt`.
+    pub fn with_salt(salt: Salt) -> LocalInterner<T> {
+        LocalInterner::with_options(Some(salt), KeyDigest::Sha1)
+    }
+}
+
+impl<T> Default for LocalInterner<T> {
+    fn default() -> LocalInterner<T> {
+        LocalInterner::new()
+    }
+}
+
+impl<T, const N: usize> LocalInterner<T, N> {
+    /// Create an interner with keys `N` bytes wide, derived with `digest` and `salt`.
+    pub fn with_options(salt: Option<Salt>, digest: KeyDigest) -> LocalInterner<T, N> {
+        LocalInterner {
+            map: RefCell::new(HashMap::default()),
+            retired: RefCell::new(Vec::new()),
+            salt: match digest {
+                KeyDigest::Sip128 => Some(salt.unwrap_or_else(Salt::random)),
+                _ => salt,
+            },
+            digest: digest,
+        }
+    }
+
+    fn key_of<B: ?Sized + Hash>(&self, data: &B) -> InternKey<N> {
+        InternKey::hash(self.digest, self.salt.as_ref(), data)
+    }
+
+    fn handle<'a>(&'a self, field: &LocalField<T, N>) -> LocalInterned<'a, T, N> {
+        field.count.set(field.count.get() + 1);
+        LocalInterned {
+            interner: self,
+            field: unsafe { extend_lifetime(field) },
+        }
+    }
+
+    /// Look up `key`, inserting the value `f` builds if it's absent. `f` runs without the
+    /// table borrowed, so it can intern into this interner itself.
+    fn intern_with<'a, F: FnOnce() -> T>(&'a self, key: InternKey<N>, f: F) -> LocalInterned<'a, T, N> {
+        if let Some(field) = self.map.borrow().get(&key) {
+            return self.handle(field);
+        }
+        let field = Box::new(LocalField {
+            key: key,
+            count: Cell::new(0),
+            data: f(),
+        });
+        let (handle, spare) = match self.map.borrow_mut().entry(field.key.clone()) {
+            Entry::Occupied(oe) => (self.handle(oe.get()), Some(field)),
+            Entry::Vacant(ve) => (self.handle(ve.insert(field)), None),
+        };
+        // Dropping a `T` can drop handles into this interner, so never do it with the table
+        // borrowed.
+        drop(spare);
+        self.reclaim();
+        handle
+    }
+
+    pub fn intern<'a>(&'a self, data: T) -> LocalInterned<'a, T, N>
+            where T: Hash
+    {
+        let key = self.key_of(&data);
+        self.intern_with(key, || data)
+    }
+
+    pub fn intern_borrowed<'a, B>(&'a self, data: &B) -> LocalInterned<'a, T, N>
+            where B: ?Sized + Hash + ToOwned<Owned=T>,
+                  T: Borrow<B>
+    {
+        let key = self.key_of(data);
+        self.intern_with(key, || data.to_owned())
+    }
+
+    /// Get a handle to the value equal to `data` if one is already interned.
+    pub fn get<'a, B: ?Sized + Hash>(&'a self, data: &B) -> Option<LocalInterned<'a, T, N>>
+            where T: Borrow<B>
+    {
+        let key = self.key_of(data);
+        let map = self.map.borrow();
+        map.get(&key).map(|field| self.handle(field))
+    }
+
+    /// Free every entry whose last handle has been dropped. Entries are otherwise reclaimed
+    /// lazily as new values are interned.
+    pub fn reclaim(&self) {
+        loop {
+            let retired = mem::take(&mut *self.retired.borrow_mut());
+            if retired.is_empty() {
+                return;
+            }
+            let mut removed = Vec::new();
+            {
+                let mut map = self.map.borrow_mut();
+                // An entry can be retired more than once, or resurrected after being retired.
+                for key in retired {
+                    if map.get(&key).is_some_and(|field| field.count.get() == 0) {
+                        removed.extend(map.remove(&key));
+                    }
+                }
+            }
+            // Dropping these may retire more entries, so go again.
+            drop(removed);
+        }
+    }
+
+    /// Drop a handle's reference to `field`, retiring it if it was the last.
+    fn release(&self, field: &LocalField<T, N>) {
+        let count = field.count.get() - 1;
+        field.count.set(count);
+        if count == 0 {
+            self.retired.borrow_mut().push(field.key.clone());
+        }
+    }
+
+    /// Intern `data` into the interner behind `this`, returning an owned handle which keeps
+    /// the interner alive rather than borrowing it.
+    pub fn intern_rc(this: &Rc<LocalInterner<T, N>>, data: T) -> RcInterned<T, N>
+            where T: Hash
+    {
+        RcInterned::new(this, this.intern(data))
+    }
+
+    /// Like `intern_rc`, for borrowed data. See `intern_borrowed`.
+    pub fn intern_borrowed_rc<B>(this: &Rc<LocalInterner<T, N>>, data: &B) -> RcInterned<T, N>
+            where B: ?Sized + Hash + ToOwned<Owned=T>,
+                  T: Borrow<B>
+    {
+        RcInterned::new(this, this.intern_borrowed(data))
+    }
+
+    /// The number of entries in the table, including ones waiting to be reclaimed.
+    pub fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a, T, const N: usize> LocalInterned<'a, T, N> {
+    /// The key the value is interned under.
+    pub fn key(&self) -> &InternKey<N> {
+        &self.field.key
+    }
+}
+
+impl<'a, T, const N: usize> Deref for LocalInterned<'a, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.field.data
+    }
+}
+
+impl<'a, T, const N: usize> Clone for LocalInterned<'a, T, N> {
+    fn clone(&self) -> LocalInterned<'a, T, N> {
+        self.field.count.set(self.field.count.get() + 1);
+        LocalInterned {
+            interner: self.interner,
+            field: self.field,
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Drop for LocalInterned<'a, T, N> {
+    fn drop(&mut self) {
+        self.interner.release(self.field);
+    }
+}
+
+impl<'a, T, const N: usize> Hash for LocalInterned<'a, T, N> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.field.key.hash(hasher)
+    }
+}
+
+impl<'a, T, const N: usize> PartialEq for LocalInterned<'a, T, N> {
+    fn eq(&self, other: &LocalInterned<'a, T, N>) -> bool {
+        self.field.key == other.field.key
+    }
+}
+
+impl<'a, T, const N: usize> Eq for LocalInterned<'a, T, N> {}
+
+impl<'a, T: fmt::Debug, const N: usize> fmt::Debug for LocalInterned<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "LocalInterned[{}] ", self.field.key));
+        self.field.data.fmt(f)
+    }
+}
+
+/// An owned handle to a value in a `LocalInterner`, which holds the interner by `Rc` so that
+/// it can be stored without a lifetime, eg. in a GUI's widgets. Values holding `RcInterned`s
+/// into their own interner form `Rc` cycles, and so are never freed.
+pub struct RcInterned<T, const N: usize = 20> {
+    interner: Rc<LocalInterner<T, N>>,
+    // Points into the entry's box, which stays put while its count is non-zero.
+    field: *const LocalField<T, N>,
+}
+
+impl<T, const N: usize> RcInterned<T, N> {
+    /// Take over `handle`'s reference to its entry.
+    fn new(interner: &Rc<LocalInterner<T, N>>, handle: LocalInterned<T, N>) -> RcInterned<T, N> {
+        let field = handle.field as *const LocalField<T, N>;
+        mem::forget(handle);
+        RcInterned {
+            interner: interner.clone(),
+            field: field,
+        }
+    }
+
+    fn field(&self) -> &LocalField<T, N> {
+        unsafe { &*self.field }
+    }
+
+    /// The key the value is interned under.
+    pub fn key(&self) -> &InternKey<N> {
+        &self.field().key
+    }
+
+    pub fn interner(&self) -> &Rc<LocalInterner<T, N>> {
+        &self.interner
+    }
+}
+
+impl<T, const N: usize> Deref for RcInterned<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.field().data
+    }
+}
+
+impl<T, const N: usize> Clone for RcInterned<T, N> {
+    fn clone(&self) -> RcInterned<T, N> {
+        let field = self.field();
+        field.count.set(field.count.get() + 1);
+        RcInterned {
+            interner: self.interner.clone(),
+            field: self.field,
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for RcInterned<T, N> {
+    fn drop(&mut self) {
+        self.interner.release(unsafe { &*self.field });
+    }
+}
+
+impl<T, const N: usize> Hash for RcInterned<T, N> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.key().hash(hasher)
+    }
+}
+
+impl<T, const N: usize> PartialEq for RcInterned<T, N> {
+    fn eq(&self, other: &RcInterned<T, N>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T, const N: usize> Eq for RcInterned<T, N> {}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for RcInterned<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "RcInterned[{}] ", self.key()));
+        self.field().data.fmt(f)
+    }
+}
+
+impl<'a, T: Hash + 'a, const N: usize> InternerApi<'a, T> for LocalInterner<T, N> {
+    type Handle = LocalInterned<'a, T, N>;
+
+    fn intern(&'a self, data: T) -> LocalInterned<'a, T, N> {
+        LocalInterner::intern(self, data)
+    }
+
+    fn try_intern(&'a self, data: T) -> Result<LocalInterned<'a, T, N>, InternError> {
+        Ok(LocalInterner::intern(self, data))
+    }
+
+    fn get(&'a self, data: &T) -> Option<LocalInterned<'a, T, N>> {
+        LocalInterner::get(self, data)
+    }
+
+    fn len(&self) -> usize {
+        LocalInterner::len(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{LocalInterner, RcInterned};
+    use Interner;
+
+    #[test]
+    fn local_interner() {
+        let interner = LocalInterner::new();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern_borrowed("b");
+        assert_eq!(interner.intern_borrowed("a"), a);
+        assert!(a != b);
+        assert_eq!(interner.get("b"), Some(b.clone()));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(a.key(), Interner::new().intern_borrowed("a").key());
+
+        drop(b);
+        interner.reclaim();
+        assert_eq!(interner.len(), 1);
+        drop(a);
+        interner.reclaim();
+        assert!(interner.is_empty());
+    }
+
+    #[test]
+    fn nested_handles() {
+        let strings = LocalInterner::new();
+        let lists = LocalInterner::new();
+        let list = lists.intern(vec![strings.intern_borrowed("x"), strings.intern_borrowed("y")]);
+        assert_eq!(strings.len(), 2);
+        drop(list);
+        lists.reclaim();
+        strings.reclaim();
+        assert!(lists.is_empty());
+        assert!(strings.is_empty());
+    }
+
+    #[test]
+    fn rc_handles() {
+        struct Widget {
+            label: RcInterned<String>,
+        }
+
+        let interner = Rc::new(LocalInterner::new());
+        let widgets: Vec<Widget> = ["ok", "cancel", "ok"].iter()
+                                                         .map(|&s| Widget { label: LocalInterner::intern_borrowed_rc(&interner, s) })
+                                                         .collect();
+        assert_eq!(widgets[0].label, widgets[2].label);
+        assert_eq!(&widgets[1].label[..], "cancel");
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.intern_borrowed("ok").key(), widgets[0].label.key());
+
+        // The handles keep the interner alive after the original `Rc` is gone.
+        let weak = Rc::downgrade(&interner);
+        drop(interner);
+        let label = widgets[0].label.clone();
+        drop(widgets);
+        assert_eq!(label.interner().len(), 2);
+        label.interner().reclaim();
+        assert_eq!(label.interner().len(), 1);
+        drop(label);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,111 @@
+//! Memoization of functions of interned values. Comparing and hashing handles is O(1), so
+//! caching results keyed by them is cheap even when the values are large trees.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner};
+
+/// Caches results keyed by the handles they were computed from. The cache doesn't hold the
+/// handles themselves, so it doesn't keep its inputs alive: entries whose inputs have all
+/// been released are dropped as the cache grows.
+pub struct Memo<'a, T: 'a, V, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    results: RefCell<HashMap<Vec<InternKey<N>>, V, BuildKeyHasher>>,
+    next_prune: Cell<usize>,
+}
+
+const MIN_PRUNE: usize = 64;
+
+impl<'a, T: 'a, V: Clone, S: BuildMap<T, N> + 'a, const N: usize> Memo<'a, T, V, S, N> {
+    pub fn new(interner: &'a Interner<T, S, N>) -> Memo<'a, T, V, S, N> {
+        Memo {
+            interner: interner,
+            results: RefCell::new(HashMap::default()),
+            next_prune: Cell::new(MIN_PRUNE),
+        }
+    }
+
+    /// Get the result cached for `inputs`, computing it with `f` if there isn't one. `f` may
+    /// use this cache itself, eg. to memoize a recursive analysis of a tree.
+    pub fn get_or_insert_with<F>(&self, inputs: &[&Interned<'a, T, S, N>], f: F) -> V
+            where F: FnOnce() -> V
+    {
+        let keys: Vec<InternKey<N>> = inputs.iter().map(|handle| handle.key().clone()).collect();
+        if let Some(result) = self.results.borrow().get(&keys) {
+            return result.clone();
+        }
+        let result = f();
+        self.results.borrow_mut().insert(keys, result.clone());
+        if self.len() >= self.next_prune.get() {
+            self.prune();
+        }
+        result
+    }
+
+    /// Drop every cached result whose inputs have all been released.
+    pub fn prune(&self) {
+        let interner = self.interner;
+        let pruned: Vec<V> = {
+            let mut results = self.results.borrow_mut();
+            let dead: Vec<Vec<InternKey<N>>> = results.keys()
+                                                   .filter(|keys| !keys.iter().any(|key| interner.is_live(key)))
+                                                   .cloned()
+                                                   .collect();
+            dead.iter().filter_map(|keys| results.remove(keys)).collect()
+        };
+        drop(pruned);
+        self.next_prune.set(::std::cmp::max(MIN_PRUNE, self.len() * 2));
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::Memo;
+    use {Interned, Interner};
+
+    #[derive(Hash)]
+    enum Tree<'i> {
+        Leaf,
+        Node(Interned<'i, Tree<'i>>, Interned<'i, Tree<'i>>),
+    }
+
+    fn size<'i>(memo: &Memo<'i, Tree<'i>, u64>, calls: &Cell<u32>, tree: &Interned<'i, Tree<'i>>) -> u64 {
+        memo.get_or_insert_with(&[tree], || {
+            calls.set(calls.get() + 1);
+            match **tree {
+                Tree::Leaf => 1,
+                Tree::Node(ref l, ref r) => 1 + size(memo, calls, l) + size(memo, calls, r),
+            }
+        })
+    }
+
+    #[test]
+    fn memoized_size() {
+        let interner: &'static Interner<Tree<'static>> = Box::leak(Box::new(Interner::new()));
+        let mut tree = interner.intern(Tree::Leaf);
+        for _ in 0..40 {
+            tree = interner.intern(Tree::Node(tree.clone(), tree.clone()));
+        }
+        let memo = Memo::new(interner);
+        let calls = Cell::new(0);
+        assert_eq!(size(&memo, &calls, &tree), (1 << 41) - 1);
+        assert_eq!(calls.get(), 41);
+
+        // Nodes hold handles to their children, so reclaim to release the whole tree.
+        drop(tree);
+        interner.reclaim().unwrap();
+        memo.prune();
+        assert!(memo.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+//! Deduplicating files by content without reading them onto the heap. A file is mapped into
+//! memory and its key hashed straight from the mapping, so a file whose contents are already
+//! interned is never copied anywhere, and one that isn't is interned as the mapping itself.
+
+use std::borrow::Borrow;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use {BuildMap, Interned, Interner};
+
+/// A file's contents, mapped into memory. Hashes and compares as its bytes, so it has the
+/// same key as the same bytes interned as a `[u8]`, and can be looked up by them.
+///
+/// The mapping is only as stable as the file, which is why making one is unsafe.
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Option<Mmap>,
+}
+
+impl MappedFile {
+    /// Map the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be truncated or modified, by this process or any other, for as long
+    /// as the `MappedFile` lives. Truncating it can make reading the mapping crash, and
+    /// changing it changes bytes behind shared references.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedFile> {
+        let file = try!(File::open(path));
+        // Mapping an empty file fails on some platforms, and there's nothing to map anyway.
+        if try!(file.metadata()).len() == 0 {
+            return Ok(MappedFile { map: None });
+        }
+        let map = try!(Mmap::map(&file));
+        Ok(MappedFile { map: Some(map) })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.map {
+            Some(ref map) => &map[..],
+            None => &[],
+        }
+    }
+}
+
+impl Borrow<[u8]> for MappedFile {
+    fn borrow(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Hash for MappedFile {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self[..].hash(hasher)
+    }
+}
+
+impl PartialEq for MappedFile {
+    fn eq(&self, other: &MappedFile) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl Eq for MappedFile {}
+
+impl<S: BuildMap<MappedFile, N>, const N: usize> Interner<MappedFile, S, N> {
+    /// Intern the contents of the file at `path`. The file is hashed from a mapping as it's
+    /// paged in, and if its contents are already interned the mapping is dropped and the
+    /// existing entry returned, so a duplicate file is only ever read once. Entries are
+    /// trusted to match by key alone, without comparing contents.
+    ///
+    /// # Safety
+    ///
+    /// As for `MappedFile::open`, except that the file has to be left alone for as long as
+    /// the entry is interned, since the mapping is kept as the entry's value.
+    pub unsafe fn intern_file<'a, P: AsRef<Path>>(&'a self, path: P) -> io::Result<Interned<'a, MappedFile, S, N>> {
+        let file = try!(MappedFile::open(path));
+        let key = self.key_of(&file);
+        self.insert_or_get(key, file, |_, _| true)
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use Interner;
+
+    #[test]
+    fn intern_file() {
+        let dir = env::temp_dir().join(format!("interner-mmap-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), b"same contents").unwrap();
+        fs::write(dir.join("b"), b"same contents").unwrap();
+        fs::write(dir.join("c"), b"other contents").unwrap();
+        fs::write(dir.join("empty"), b"").unwrap();
+
+        let interner = Interner::new();
+        // Nothing else touches the files until they're removed below.
+        let (a, b, c, empty) = unsafe {
+            (interner.intern_file(dir.join("a")).unwrap(),
+             interner.intern_file(dir.join("b")).unwrap(),
+             interner.intern_file(dir.join("c")).unwrap(),
+             interner.intern_file(dir.join("empty")).unwrap())
+        };
+        assert_eq!(a, b);
+        assert!(a != c);
+        assert_eq!(&a[..], b"same contents");
+        assert_eq!(empty.len(), 0);
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.get(&b"other contents"[..]), Some(c));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
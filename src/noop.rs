@@ -0,0 +1,78 @@
+//! An `InternerApi` implementation that doesn't intern anything, for measuring what
+//! interning actually buys an application.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+use {InternerApi, InternError};
+
+/// Hands every value straight back in its own `Arc` without deduplicating it. Swap this in
+/// for an `Interner` in code written against `InternerApi` to compare the two.
+pub struct NoopInterner<T> {
+    count: AtomicUsize,
+    _ph: PhantomData<fn(T)>,
+}
+
+impl<T> NoopInterner<T> {
+    pub fn new() -> NoopInterner<T> {
+        NoopInterner {
+            count: AtomicUsize::new(0),
+            _ph: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for NoopInterner<T> {
+    fn default() -> NoopInterner<T> {
+        NoopInterner::new()
+    }
+}
+
+impl<'a, T> InternerApi<'a, T> for NoopInterner<T> {
+    type Handle = Arc<T>;
+
+    fn intern(&'a self, data: T) -> Arc<T> {
+        self.count.fetch_add(1, Relaxed);
+        Arc::new(data)
+    }
+
+    fn try_intern(&'a self, data: T) -> Result<Arc<T>, InternError> {
+        Ok(InternerApi::intern(self, data))
+    }
+
+    /// Nothing is ever stored, so there's never anything to get.
+    fn get(&'a self, _: &T) -> Option<Arc<T>> {
+        None
+    }
+
+    /// The number of values that have been passed through, since none are deduplicated.
+    fn len(&self) -> usize {
+        self.count.load(Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoopInterner;
+    use {Interner, InternerApi};
+
+    fn intern_twice<'a, I: InternerApi<'a, u32>>(api: &'a I) -> (I::Handle, I::Handle) {
+        (api.intern(7), api.intern(7))
+    }
+
+    #[test]
+    fn swap_type() {
+        let interner = Interner::new();
+        let (a, b) = intern_twice(&interner);
+        assert_eq!(*a, *b);
+        assert_eq!(interner.len(), 1);
+
+        let noop = NoopInterner::new();
+        let (a, b) = intern_twice(&noop);
+        assert_eq!(*a, *b);
+        assert_eq!(noop.len(), 2);
+        assert!(noop.get(&7).is_none());
+    }
+}
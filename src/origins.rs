@@ -0,0 +1,91 @@
+//! Recording where each live handle was made, for answering "what's keeping this entry
+//! alive?" during leak hunts. Capturing a backtrace for every handle is slow, so this is
+//! only compiled in with the `debug-handles` feature.
+
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildMap, InternKey, Interner};
+
+/// Where a live handle was made, from `Interner::live_handle_origins`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HandleOrigin<const N: usize = 20> {
+    pub key: InternKey<N>,
+    /// The backtrace of the `intern`, `get`, `clone` or such that made the handle.
+    pub backtrace: String,
+}
+
+/// The origins of an interner's live handles, by handle id.
+pub(crate) struct Origins<const N: usize> {
+    next_id: AtomicUsize,
+    live: Mutex<HashMap<usize, (InternKey<N>, Backtrace)>>,
+}
+
+impl<const N: usize> Origins<N> {
+    pub(crate) fn new() -> Origins<N> {
+        Origins {
+            next_id: AtomicUsize::new(0),
+            live: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a new handle to `key`, returning its id.
+    pub(crate) fn record(&self, key: &InternKey<N>) -> usize {
+        let id = self.next_id.fetch_add(1, Relaxed);
+        let backtrace = Backtrace::force_capture();
+        self.live.lock().unwrap().insert(id, (key.clone(), backtrace));
+        id
+    }
+
+    /// Forget the handle with id `id`, which has been released.
+    pub(crate) fn release(&self, id: usize) {
+        self.live.lock().unwrap().remove(&id);
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Where every live handle was made, sorted by key and then by age. Handles given up
+    /// by `Interned::pin` or `preload` aren't included.
+    pub fn live_handle_origins(&self) -> Vec<HandleOrigin<N>> {
+        let live = self.origins.live.lock().unwrap();
+        let mut origins: Vec<(&usize, &(InternKey<N>, Backtrace))> = live.iter().collect();
+        origins.sort_by(|a, b| ((a.1).0.cmp(&(b.1).0)).then(a.0.cmp(b.0)));
+        origins.into_iter()
+               .map(|(_, (key, backtrace))| {
+                   HandleOrigin {
+                       key: key.clone(),
+                       backtrace: backtrace.to_string(),
+                   }
+               })
+               .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn live_handle_origins() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let a2 = a.clone();
+        let b = interner.intern(String::from("b"));
+        assert_eq!(interner.live_handle_origins().len(), 3);
+        drop(a);
+        drop(b);
+        let origins = interner.live_handle_origins();
+        assert_eq!(origins.len(), 1);
+        assert_eq!(&origins[0].key, a2.key());
+        assert!(origins[0].backtrace.contains("live_handle_origins"));
+        a2.try_release().unwrap();
+        assert!(interner.live_handle_origins().is_empty());
+
+        interner.preload(vec![String::from("c")]).unwrap();
+        interner.intern(String::from("d")).pin();
+        assert!(interner.live_handle_origins().is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+//! Interning pairs and triples of handles, for relational data such as edges and mappings.
+//! Like sequences, tuples are keyed by their elements' keys rather than their values.
+
+use {BuildMap, Interned, Interner, MapBackend};
+
+/// The value `intern_pair` interns.
+type Pair<'a, A, B, SA, SB, const NA: usize, const NB: usize> = (Interned<'a, A, SA, NA>, Interned<'a, B, SB, NB>);
+
+/// The value `intern_triple` interns.
+type Triple<'a, A, B, C, SA, SB, SC, const NA: usize, const NB: usize, const NC: usize> =
+    (Interned<'a, A, SA, NA>, Interned<'a, B, SB, NB>, Interned<'a, C, SC, NC>);
+
+impl<'a, A: 'a, B: 'a, SA, SB, S, const NA: usize, const NB: usize, const N: usize>
+        Interner<Pair<'a, A, B, SA, SB, NA, NB>, S, N>
+        where SA: BuildMap<A, NA> + 'a,
+              SB: BuildMap<B, NB> + 'a,
+              S: BuildMap<Pair<'a, A, B, SA, SB, NA, NB>, N>
+{
+    /// Intern the pair `(a, b)`. The handles are only cloned if it isn't interned yet.
+    pub fn intern_pair<'p>(&'p self, a: &Interned<'a, A, SA, NA>, b: &Interned<'a, B, SB, NB>)
+            -> Interned<'p, Pair<'a, A, B, SA, SB, NA, NB>, S, N>
+    {
+        // A tuple of references hashes just like the tuple of values.
+        self.intern_borrowed_with(&(a, b), |&(a, b)| (a.clone(), b.clone()))
+    }
+
+    /// Get the pair `(a, b)` if it's interned.
+    pub fn get_pair<'p>(&'p self, a: &Interned<'a, A, SA, NA>, b: &Interned<'a, B, SB, NB>)
+            -> Option<Interned<'p, Pair<'a, A, B, SA, SB, NA, NB>, S, N>>
+    {
+        let key = self.key_of(&(a, b));
+        let map = self.read(&key).unwrap();
+        map.get(&key).map(|field| self.handle(field))
+    }
+}
+
+impl<'a, A: 'a, B: 'a, C: 'a, SA, SB, SC, S, const NA: usize, const NB: usize, const NC: usize, const N: usize>
+        Interner<Triple<'a, A, B, C, SA, SB, SC, NA, NB, NC>, S, N>
+        where SA: BuildMap<A, NA> + 'a,
+              SB: BuildMap<B, NB> + 'a,
+              SC: BuildMap<C, NC> + 'a,
+              S: BuildMap<Triple<'a, A, B, C, SA, SB, SC, NA, NB, NC>, N>
+{
+    /// Intern the triple `(a, b, c)`. The handles are only cloned if it isn't interned yet.
+    pub fn intern_triple<'p>(&'p self, a: &Interned<'a, A, SA, NA>, b: &Interned<'a, B, SB, NB>,
+                             c: &Interned<'a, C, SC, NC>)
+            -> Interned<'p, Triple<'a, A, B, C, SA, SB, SC, NA, NB, NC>, S, N>
+    {
+        self.intern_borrowed_with(&(a, b, c), |&(a, b, c)| (a.clone(), b.clone(), c.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn intern_pair() {
+        let nodes = Interner::new();
+        let edges = Interner::new();
+        let a = nodes.intern(String::from("a"));
+        let b = nodes.intern(String::from("b"));
+        let ab = edges.intern_pair(&a, &b);
+        assert_eq!(edges.intern((a.clone(), b.clone())), ab);
+        assert_eq!(edges.get_pair(&a, &b), Some(ab.clone()));
+        assert_eq!(edges.get_pair(&b, &a), None);
+        assert!(edges.intern_pair(&b, &a) != ab);
+
+        let triples = Interner::new();
+        let abb = triples.intern_triple(&a, &b, &b);
+        assert_eq!(triples.intern((a, b.clone(), b)), abb);
+    }
+}
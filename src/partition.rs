@@ -0,0 +1,64 @@
+//! Splitting an interner's entries between two new interners.
+
+use {BuildMap, InternField, InternKey, Interner, MapBackend};
+
+impl<T, S: BuildMap<T, N> + Default, const N: usize> Interner<T, S, N> {
+    /// Move the entries for which `pred` returns true into one new interner and the rest
+    /// into another, eg. to persist a language's keywords apart from transient identifiers.
+    /// Entries keep their keys and epochs. The interner is consumed, so the only handles left
+    /// are ones given up for good, by `mem::forget` or by pinning with `preload`,
+    /// `intern_permanent` or `Interned::pin`. Those aren't carried over: every entry starts
+    /// with no handles, and pinned entries aren't pinned in the halves. Both halves are
+    /// configured like `snapshot`'s copies.
+    pub fn partition<F>(mut self, mut pred: F) -> (Interner<T, S, N>, Interner<T, S, N>)
+            where F: FnMut(&T) -> bool
+    {
+        let mut yes = self.empty_like();
+        let mut no = self.empty_like();
+        for index in 0..self.shards.len() {
+            let map = self.shards[index].get_mut().unwrap();
+            let keys: Vec<InternKey<N>> = map.iter().map(|field| field.key.clone()).collect();
+            for key in keys {
+                let mut field: Box<InternField<T, N>> = map.remove(&key).unwrap();
+                // Only leaked and pinned handles can be left.
+                *field.count.get_mut() = 0;
+                *field.pinned.get_mut() = 0;
+                if pred(&field.data) {
+                    yes.adopt(index, field);
+                } else {
+                    no.adopt(index, field);
+                }
+            }
+        }
+        (yes, no)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn partition() {
+        let interner = Interner::builder().shards(4).mark_and_sweep().build();
+        for word in &["fn", "let", "foo", "bar", "baz"] {
+            drop(interner.intern_borrowed(*word));
+        }
+        let key = interner.get("foo").unwrap().key().clone();
+        let (keywords, idents) = interner.partition(|s: &String| s.len() < 3 || s == "let");
+        assert_eq!(keywords.len(), 2);
+        assert_eq!(idents.len(), 3);
+        assert_eq!(idents.stats().entries, 3);
+        assert!(keywords.get("fn").is_some());
+        assert!(keywords.get("foo").is_none());
+        assert_eq!(idents.intern_borrowed("foo").key(), &key);
+        assert_eq!(idents.len(), 3);
+
+        // Pins don't survive, so a preloaded entry can be collected like any other.
+        let interner = Interner::new();
+        interner.preload(vec![String::from("fn")]).unwrap();
+        let (keywords, _) = interner.partition(|s: &String| s == "fn");
+        assert!(keywords.verify_integrity().unwrap().is_ok());
+        assert_eq!(keywords.collect().unwrap(), 1);
+    }
+}
@@ -0,0 +1,239 @@
+//! A perfect hash over a fixed set of values, built when the interner is, which gives
+//! their keys without computing any digests.
+//!
+//! Keys are digests of the bytes a value's `Hash` impl writes, so two values which write
+//! the same bytes have the same key. The table maps those bytes to keys precomputed with
+//! the interner's digest and salt, and a lookup records the bytes of the value being
+//! interned and checks whether they're in the table.
+
+use std::cmp::Reverse;
+use std::hash::{Hash, Hasher};
+
+use {BuildMap, InternKey, Interner};
+
+/// Values which hash as more bytes than this aren't put in the table.
+pub(crate) const MAX_STREAM: usize = 64;
+
+/// Records the bytes a value's `Hash` impl writes, up to `MAX_STREAM` of them.
+struct Record {
+    bytes: [u8; MAX_STREAM],
+    len: usize,
+    overflowed: bool,
+}
+
+impl Hasher for Record {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.overflowed || self.len + bytes.len() > MAX_STREAM {
+            self.overflowed = true;
+            return;
+        }
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn finish(&self) -> u64 {
+        0
+    }
+}
+
+/// The bytes `data` hashes as, or `None` if there are too many.
+pub(crate) fn record<T: ?Sized + Hash>(data: &T) -> Option<Vec<u8>> {
+    let mut record = Record {
+        bytes: [0; MAX_STREAM],
+        len: 0,
+        overflowed: false,
+    };
+    data.hash(&mut record);
+    if record.overflowed {
+        None
+    } else {
+        Some(record.bytes[..record.len].to_vec())
+    }
+}
+
+/// Hashes as exactly the bytes it holds, so that its key is the key of the value that
+/// wrote them.
+struct Stream<'a>(&'a [u8]);
+
+impl<'a> Hash for Stream<'a> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        hasher.write(self.0)
+    }
+}
+
+/// A seeded 64-bit FNV-1a, finished with a mixer so that every bit depends on every byte.
+fn hash(seed: u64, bytes: &[u8]) -> u64 {
+    let mut h = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for &b in bytes {
+        h = (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// The bucket and the two slot hashes of a stream.
+fn hashes(seed: u64, bytes: &[u8], buckets: usize) -> (usize, u32, u32) {
+    let a = hash(seed, bytes);
+    let b = hash(seed ^ 0x9e37_79b9_7f4a_7c15, bytes);
+    (((a >> 32) as usize) % buckets, a as u32, b as u32)
+}
+
+fn slot(f1: u32, f2: u32, (d1, d2): (u32, u32), slots: usize) -> usize {
+    (d2 as u64).wrapping_add(f1 as u64).wrapping_add((f2 as u64).wrapping_mul(d1 as u64)) as usize % slots
+}
+
+/// A CHD ("compress, hash and displace") perfect hash: each stream falls in a bucket, and
+/// each bucket has a displacement chosen so that its streams land in free slots.
+#[derive(Clone)]
+pub(crate) struct PerfectHash<const N: usize> {
+    seed: u64,
+    displacements: Vec<(u32, u32)>,
+    slots: Vec<(Box<[u8]>, InternKey<N>)>,
+}
+
+impl<const N: usize> PerfectHash<N> {
+    /// Build a table of `streams` and their keys, as made by `key_of`. Duplicates are
+    /// ignored.
+    pub(crate) fn new<F>(mut streams: Vec<Vec<u8>>, key_of: F) -> PerfectHash<N>
+            where F: Fn(&[u8]) -> InternKey<N>
+    {
+        streams.sort();
+        streams.dedup();
+        let buckets = streams.len().div_ceil(5);
+        let slots = streams.len();
+        for seed in 0.. {
+            if let Some(displacements) = PerfectHash::<N>::displace(seed, &streams, buckets) {
+                let mut table: Vec<Option<(Box<[u8]>, InternKey<N>)>> = streams.iter().map(|_| None).collect();
+                for stream in streams {
+                    let (g, f1, f2) = hashes(seed, &stream, buckets);
+                    let key = key_of(&stream);
+                    table[slot(f1, f2, displacements[g], slots)] = Some((stream.into_boxed_slice(), key));
+                }
+                return PerfectHash {
+                    seed: seed,
+                    displacements: displacements,
+                    slots: table.into_iter().map(Option::unwrap).collect(),
+                };
+            }
+        }
+        unreachable!()
+    }
+
+    /// Find a displacement for each bucket, biggest buckets first, or `None` if there's a
+    /// bucket none of them work for.
+    fn displace(seed: u64, streams: &[Vec<u8>], buckets: usize) -> Option<Vec<(u32, u32)>> {
+        let slots = streams.len();
+        let mut by_bucket: Vec<Vec<(u32, u32)>> = (0..buckets).map(|_| Vec::new()).collect();
+        for stream in streams {
+            let (g, f1, f2) = hashes(seed, stream, buckets);
+            by_bucket[g].push((f1, f2));
+        }
+        let mut order: Vec<usize> = (0..buckets).collect();
+        order.sort_by_key(|&g| Reverse(by_bucket[g].len()));
+
+        let mut displacements = vec![(0, 0); buckets];
+        let mut taken = vec![false; slots];
+        let mut chosen = Vec::new();
+        for g in order {
+            let found = (0..slots as u32).flat_map(|d1| (0..slots as u32).map(move |d2| (d1, d2))).find(|&d| {
+                chosen.clear();
+                for &(f1, f2) in &by_bucket[g] {
+                    let i = slot(f1, f2, d, slots);
+                    if taken[i] || chosen.contains(&i) {
+                        return false;
+                    }
+                    chosen.push(i);
+                }
+                true
+            });
+            match found {
+                Some(d) => {
+                    displacements[g] = d;
+                    for &i in &chosen {
+                        taken[i] = true;
+                    }
+                },
+                None => return None,
+            }
+        }
+        Some(displacements)
+    }
+
+    /// The key of the value which hashes as `bytes`, if it's in the table.
+    fn get(&self, bytes: &[u8]) -> Option<&InternKey<N>> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let (g, f1, f2) = hashes(self.seed, bytes, self.displacements.len());
+        let (ref stream, ref key) = self.slots[slot(f1, f2, self.displacements[g], self.slots.len())];
+        if &stream[..] == bytes {
+            Some(key)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Build the perfect hash table over `streams`, with keys derived as `key_of` would.
+    pub(crate) fn build_perfect_hash(&mut self, streams: Vec<Vec<u8>>) {
+        let phf = PerfectHash::new(streams, |stream| self.digest_key(&Stream(stream)));
+        self.perfect_hash = Some(phf);
+    }
+
+    /// `data`'s key if it's in the perfect hash table.
+    pub(crate) fn perfect_key<B: ?Sized + Hash>(&self, data: &B) -> Option<InternKey<N>> {
+        let phf = match self.perfect_hash {
+            Some(ref phf) => phf,
+            None => return None,
+        };
+        let mut record = Record {
+            bytes: [0; MAX_STREAM],
+            len: 0,
+            overflowed: false,
+        };
+        data.hash(&mut record);
+        if record.overflowed {
+            return None;
+        }
+        phf.get(&record.bytes[..record.len]).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record, PerfectHash};
+    use {InternKey, Interner, KeyDigest, Salt};
+
+    #[test]
+    fn perfect_hash() {
+        let words: Vec<String> = (0..200).map(|i| format!("kw{}", i)).collect();
+        let streams: Vec<Vec<u8>> = words.iter().map(|w| record(&w[..]).unwrap()).collect();
+        let phf = PerfectHash::<20>::new(streams.clone(), |s| InternKey::from_bytes([s.len() as u8; 20]));
+        for stream in &streams {
+            assert_eq!(phf.get(stream), Some(&InternKey::from_bytes([stream.len() as u8; 20])));
+        }
+        assert_eq!(phf.get(&record("nope").unwrap()), None);
+    }
+
+    #[test]
+    fn perfect_keys() {
+        let keywords = ["fn", "let", "match", "if", "else"];
+        for &digest in &[KeyDigest::Sha1, KeyDigest::Fast128, KeyDigest::Sip128] {
+            let salt = Salt::new([7; 16]);
+            let plain = Interner::builder().key_digest(digest).salt(salt.clone()).build();
+            let fast = Interner::builder()
+                                .key_digest(digest)
+                                .salt(salt)
+                                .perfect_hash(keywords.iter().map(|&kw| String::from(kw)))
+                                .build();
+            for kw in keywords.iter().chain(&["x", "iff"]) {
+                assert_eq!(fast.perfect_key(*kw).is_some(), keywords.contains(kw));
+                assert_eq!(fast.intern_borrowed(*kw).key(), plain.intern_borrowed(*kw).key());
+            }
+        }
+    }
+}
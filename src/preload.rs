@@ -0,0 +1,77 @@
+//! Warming an interner up with values known to be hot, such as a language's keywords.
+
+use std::hash::Hash;
+use std::mem;
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildMap, InternError, Interned, Interner};
+
+impl<T: Hash, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Intern each of `values` and pin it, so that it's never removed even when it has no
+    /// other handles. Traffic interning these values later always finds them, and never
+    /// pays to insert or remove them. Pinned entries count as having a handle, so
+    /// `Interner::clear` will refuse to clear an interner with any.
+    pub fn preload<I>(&self, values: I) -> Result<(), InternError>
+            where I: IntoIterator<Item=T>
+    {
+        for data in values {
+            let key = self.key_of(&data);
+            try!(self.insert_or_get(key, data, |_, _| true)).pin();
+        }
+        Ok(())
+    }
+
+    /// Intern `data` and pin it, returning a plain reference to the interned value. For code
+    /// which only wants the deduplicated reference, this avoids carrying an `Interned` around
+    /// and paying to drop it. The entry is never removed.
+    pub fn intern_permanent<'a>(&'a self, data: T) -> &'a T
+            where T: 'a
+    {
+        self.intern(data).pin()
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// Pin the entry, giving up the handle for a plain reference to the value. See `preload`.
+    pub fn pin(self) -> &'a T {
+        let field = self.field;
+        field.pinned.fetch_add(1, Relaxed);
+        #[cfg(feature = "debug-handles")]
+        self.interner.origins.release(self.origin);
+        mem::forget(self);
+        &field.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use Interner;
+
+    #[test]
+    fn preload() {
+        let interner = Interner::new();
+        let keywords = ["fn", "let", "match"];
+        interner.preload(keywords.iter().map(|&kw| String::from(kw))).unwrap();
+        let before = interner.stats().peak_entries;
+        for kw in &keywords {
+            drop(interner.intern_borrowed(*kw));
+        }
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.stats().peak_entries, before);
+        assert!(interner.clear().is_err());
+    }
+
+    #[test]
+    fn intern_permanent() {
+        let interner = Interner::new();
+        let a: &String = interner.intern_permanent(String::from("a"));
+        let b = interner.intern_permanent(String::from("a"));
+        assert!(ptr::eq(a, b));
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 1);
+        assert!(ptr::eq(&*interner.intern_borrowed("a"), a));
+    }
+}
@@ -0,0 +1,177 @@
+//! An adapter with the interface of `lasso`'s `Rodeo`, for code written against that crate.
+//!
+//! A `Rodeo` holds a handle to each string it's been given, so the strings stay interned
+//! for as long as it's alive and are released when it's dropped.
+
+use std::collections::HashMap;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner};
+
+/// A key for a string in a `Rodeo`. Keys are small and `Copy`, but only mean anything to
+/// the `Rodeo` which made them.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Spur(u32);
+
+impl Spur {
+    pub fn into_usize(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn try_from_usize(index: usize) -> Option<Spur> {
+        if index < u32::MAX as usize {
+            Some(Spur(index as u32))
+        } else {
+            None
+        }
+    }
+}
+
+/// Resolves `Spur`s to strings, like `lasso::Resolver`.
+pub trait Resolver {
+    fn resolve(&self, key: &Spur) -> &str {
+        match self.try_resolve(key) {
+            Some(s) => s,
+            None => panic!("key {:?} isn't in this resolver", key),
+        }
+    }
+
+    fn try_resolve(&self, key: &Spur) -> Option<&str>;
+
+    fn contains_key(&self, key: &Spur) -> bool {
+        self.try_resolve(key).is_some()
+    }
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Interns strings into an `Interner<String>` and hands out `Spur`s for them.
+pub struct Rodeo<'a, S: BuildMap<String, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<String, S, N>,
+    strings: Vec<Interned<'a, String, S, N>>,
+    spurs: HashMap<InternKey<N>, Spur, BuildKeyHasher>,
+}
+
+impl<S: BuildMap<String, N>, const N: usize> Interner<String, S, N> {
+    /// Make a `Rodeo` which interns into this interner.
+    pub fn rodeo<'a>(&'a self) -> Rodeo<'a, S, N> {
+        Rodeo {
+            interner: self,
+            strings: Vec::new(),
+            spurs: HashMap::default(),
+        }
+    }
+}
+
+impl<'a, S: BuildMap<String, N> + 'a, const N: usize> Rodeo<'a, S, N> {
+    /// The key for `val`, interning it if this `Rodeo` hasn't seen it yet. Panics if the
+    /// `Rodeo` runs out of keys.
+    pub fn get_or_intern<V: AsRef<str>>(&mut self, val: V) -> Spur {
+        match self.try_get_or_intern(val) {
+            Some(spur) => spur,
+            None => panic!("the rodeo has run out of keys"),
+        }
+    }
+
+    /// Like `get_or_intern`, but returns `None` rather than panicking.
+    pub fn try_get_or_intern<V: AsRef<str>>(&mut self, val: V) -> Option<Spur> {
+        let handle = self.interner.intern_borrowed(val.as_ref());
+        if let Some(&spur) = self.spurs.get(handle.key()) {
+            return Some(spur);
+        }
+        let spur = Spur::try_from_usize(self.strings.len());
+        if let Some(spur) = spur {
+            self.spurs.insert(handle.key().clone(), spur);
+            self.strings.push(handle);
+        }
+        spur
+    }
+
+    /// `lasso` stores `'static` strings without copying them. Here they're copied into the
+    /// interner like any other.
+    pub fn get_or_intern_static(&mut self, val: &'static str) -> Spur {
+        self.get_or_intern(val)
+    }
+
+    /// The key for `val` if this `Rodeo` has interned it.
+    pub fn get<V: AsRef<str>>(&self, val: V) -> Option<Spur> {
+        let key = self.interner.key_of(val.as_ref());
+        self.spurs.get(&key).cloned()
+    }
+
+    pub fn contains<V: AsRef<str>>(&self, val: V) -> bool {
+        self.get(val).is_some()
+    }
+
+    /// The handle behind `key`, for code moving from keys to handles.
+    pub fn handle(&self, key: &Spur) -> Option<&Interned<'a, String, S, N>> {
+        self.strings.get(key.into_usize())
+    }
+
+    /// Every key and its string, in the order they were interned.
+    pub fn iter<'r>(&'r self) -> Box<dyn Iterator<Item=(Spur, &'r str)> + 'r> {
+        Box::new(self.strings.iter().enumerate().map(|(i, s)| (Spur(i as u32), &s[..])))
+    }
+
+    /// Give up interning, keeping the ability to resolve keys.
+    pub fn into_resolver(self) -> RodeoResolver<'a, S, N> {
+        RodeoResolver {
+            strings: self.strings,
+        }
+    }
+}
+
+impl<'a, S: BuildMap<String, N> + 'a, const N: usize> Resolver for Rodeo<'a, S, N> {
+    fn try_resolve(&self, key: &Spur) -> Option<&str> {
+        self.strings.get(key.into_usize()).map(|s| &s[..])
+    }
+
+    fn len(&self) -> usize {
+        self.strings.len()
+    }
+}
+
+/// A frozen `Rodeo` which can only resolve keys, like `lasso::RodeoResolver`.
+pub struct RodeoResolver<'a, S: BuildMap<String, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    strings: Vec<Interned<'a, String, S, N>>,
+}
+
+impl<'a, S: BuildMap<String, N> + 'a, const N: usize> Resolver for RodeoResolver<'a, S, N> {
+    fn try_resolve(&self, key: &Spur) -> Option<&str> {
+        self.strings.get(key.into_usize()).map(|s| &s[..])
+    }
+
+    fn len(&self) -> usize {
+        self.strings.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+    use Interner;
+
+    #[test]
+    fn rodeo() {
+        let interner = Interner::new();
+        let mut rodeo = interner.rodeo();
+        let a = rodeo.get_or_intern("a");
+        let b = rodeo.get_or_intern(String::from("b"));
+        assert_eq!(rodeo.get_or_intern("a"), a);
+        assert!(a != b);
+        assert_eq!(rodeo.get("b"), Some(b));
+        assert_eq!(rodeo.get("c"), None);
+        assert_eq!(rodeo.resolve(&a), "a");
+        assert_eq!(rodeo.len(), 2);
+        assert_eq!(interner.get("a"), rodeo.handle(&a).cloned());
+
+        let resolver = rodeo.into_resolver();
+        assert_eq!(resolver.resolve(&b), "b");
+        drop(resolver);
+        interner.reclaim().unwrap();
+        assert!(interner.is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+//! Interning sequences of handles, such as argument lists or the elements of hash-consed
+//! vectors. A sequence is keyed by its elements' keys, never their values, so interning
+//! one costs the same however large the elements are.
+
+use {BuildKeyHasher, BuildMap, Interned, Interner};
+
+/// A handle to an interned sequence of handles.
+pub type Seq<'b, 'a, T, S = BuildKeyHasher, const N: usize = 20> = Interned<'b, Vec<Interned<'a, T, S, N>>, S, N>;
+
+impl<'a, T: 'a, S, S2, const N: usize, const M: usize> Interner<Vec<Interned<'a, T, S, N>>, S2, M>
+        where S: BuildMap<T, N> + 'a,
+              S2: BuildMap<Vec<Interned<'a, T, S, N>>, M>
+{
+    /// Intern the sequence `items`. The handles are only cloned if it isn't interned yet.
+    pub fn intern_seq<'b>(&'b self, items: &[Interned<'a, T, S, N>]) -> Interned<'b, Vec<Interned<'a, T, S, N>>, S2, M> {
+        self.intern_borrowed(items)
+    }
+
+    /// Intern the sequence of handles `items` yields.
+    pub fn intern_seq_from<'b, I>(&'b self, items: I) -> Interned<'b, Vec<Interned<'a, T, S, N>>, S2, M>
+            where I: IntoIterator<Item=Interned<'a, T, S, N>>
+    {
+        self.intern(items.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Interner, Seq};
+
+    #[test]
+    fn intern_seq() {
+        let strings = Interner::new();
+        let seqs = Interner::new();
+        let a = strings.intern(String::from("a"));
+        let b = strings.intern(String::from("b"));
+        let ab: Seq<String> = seqs.intern_seq(&[a.clone(), b.clone()]);
+        assert_eq!(seqs.intern_seq_from(vec![a.clone(), b.clone()]), ab);
+        assert!(seqs.intern_seq(&[b.clone(), a.clone()]) != ab);
+        assert_eq!(seqs.len(), 2);
+
+        // The sequence keeps its elements alive.
+        drop((a, b));
+        strings.reclaim().unwrap();
+        assert_eq!(strings.len(), 2);
+        assert_eq!(&*ab[1], "b");
+    }
+}
@@ -0,0 +1,66 @@
+//! Comparing the contents of two interners by key, without touching their values.
+//!
+//! Keys are compared as they are, so these only make sense for interners which key values
+//! the same way, ie. with the same salt and digest. Otherwise no keys will match.
+
+use {BuildMap, InternKey, Interner, MapBackend};
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Every key in the table, sorted. Each shard is read-locked in turn, so entries added
+    /// or removed meanwhile may or may not be included. Collecting the keys first means the
+    /// other interner is never locked while this one is.
+    fn sorted_keys(&self) -> Vec<InternKey<N>> {
+        let mut keys = Vec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            keys.extend(map.iter().map(|field| field.key.clone()));
+        }
+        keys.sort();
+        keys
+    }
+
+    fn contains_key(&self, key: &InternKey<N>) -> bool {
+        self.read(key).unwrap().get(key).is_some()
+    }
+
+    /// The keys of the entries in both this interner and `other`, sorted.
+    pub fn intersection_keys<S2: BuildMap<T, N>>(&self, other: &Interner<T, S2, N>) -> Vec<InternKey<N>> {
+        let mut keys = self.sorted_keys();
+        keys.retain(|key| other.contains_key(key));
+        keys
+    }
+
+    /// The keys of the entries in this interner but not in `other`, sorted.
+    pub fn difference_keys<S2: BuildMap<T, N>>(&self, other: &Interner<T, S2, N>) -> Vec<InternKey<N>> {
+        let mut keys = self.sorted_keys();
+        keys.retain(|key| !other.contains_key(key));
+        keys
+    }
+
+    /// Whether every entry in this interner is also in `other`.
+    pub fn is_subset<S2: BuildMap<T, N>>(&self, other: &Interner<T, S2, N>) -> bool {
+        self.sorted_keys().iter().all(|key| other.contains_key(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn set_ops() {
+        let a = Interner::new();
+        let b = Interner::builder().shards(1).build();
+        let _a: Vec<_> = ["x", "y", "z"].iter().map(|s| a.intern_borrowed(*s)).collect();
+        let _b: Vec<_> = ["y", "z"].iter().map(|s| b.intern_borrowed(*s)).collect();
+
+        let mut shared = vec![a.get("y").unwrap().key().clone(), a.get("z").unwrap().key().clone()];
+        shared.sort();
+        assert_eq!(a.intersection_keys(&b), shared);
+        assert_eq!(b.intersection_keys(&a), shared);
+        assert_eq!(a.difference_keys(&b), [a.get("x").unwrap().key().clone()]);
+        assert!(b.difference_keys(&a).is_empty());
+        assert!(b.is_subset(&a));
+        assert!(!a.is_subset(&b));
+    }
+}
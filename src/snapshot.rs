@@ -0,0 +1,104 @@
+//! Point-in-time copies of an interner.
+
+use std::sync::atomic::Ordering::Relaxed;
+
+use {BuildMap, InternField, Interner, MapBackend};
+
+impl<T, S: BuildMap<T, N> + Default, const N: usize> Interner<T, S, N> {
+    /// An empty interner which keys values the same way as this one, with the same sharding,
+    /// locking, limits, cache mode and `InconsistencyPolicy`. Side tables (stable ids,
+    /// symbols, access statistics), the eviction policy and the `on_evict` and `on_leak`
+    /// callbacks aren't carried over.
+    pub(crate) fn empty_like(&self) -> Interner<T, S, N> {
+        let mut builder = Interner::builder_with_key_bytes::<N>()
+                                   .backend(S::default())
+                                   .key_digest(self.digest)
+                                   .shards(self.shards.len())
+                                   .shard_strategy(self.shard_strategy)
+                                   .lock_strategy(self.lock_strategy)
+                                   .on_inconsistency(self.inconsistency);
+        if let Some(ref salt) = self.salt {
+            builder = builder.salt(salt.clone());
+        }
+        if let Some(ref salt) = self.export_salt {
+            builder = builder.export_salt(salt.clone());
+        }
+        if let Some(heap_size) = self.heap_size {
+            builder = builder.heap_size(heap_size);
+        }
+        if let Some(max) = self.max_entries {
+            builder = builder.max_entries(max);
+        }
+        if let Some(max) = self.max_bytes {
+            builder = builder.max_bytes(max);
+        }
+        if self.mark_and_sweep {
+            builder = builder.mark_and_sweep();
+        }
+        let mut interner = builder.build();
+        interner.perfect_hash = self.perfect_hash.clone();
+        interner.epoch.store(self.epoch.load(Relaxed), Relaxed);
+        interner
+    }
+
+    /// Add an entry from an interner made by `empty_like` (or the one it was made from),
+    /// into the same shard it was in there. The entry already fit under that interner's
+    /// limits, but values with interior mutability may have grown since, so it's counted
+    /// without checking them.
+    pub(crate) fn adopt(&mut self, index: usize, field: Box<InternField<T, N>>) {
+        let max_entries = self.max_entries.take();
+        let max_bytes = self.max_bytes.take();
+        // With no limits set, this can't fail.
+        let _ = self.admit(&field);
+        self.max_entries = max_entries;
+        self.max_bytes = max_bytes;
+        self.shards[index].get_mut().unwrap().get_or_insert(field);
+    }
+
+    /// Copy every entry into a new interner, eg. for a worker to analyse or change without
+    /// holding up interning into this one. Every shard is read-locked while the entries are
+    /// cloned, so the copy is consistent. Entries keep their keys and epochs but start with
+    /// no handles, like those added by `import_sorted`. The copy keys values the same way and
+    /// has the same sharding, limits and cache mode, but not the side tables, eviction policy
+    /// or callbacks.
+    pub fn snapshot(&self) -> Interner<T, S, N>
+            where T: Clone
+    {
+        let mut copy = self.empty_like();
+        let maps: Vec<_> = self.shards.iter().map(|shard| shard.read().unwrap()).collect();
+        for (index, map) in maps.iter().enumerate() {
+            for entry in map.iter() {
+                let field = Box::new(InternField::new(entry.key.clone(), entry.data.clone()));
+                field.epoch.store(entry.epoch.load(Relaxed), Relaxed);
+                copy.adopt(index, field);
+            }
+        }
+        copy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn snapshot() {
+        let interner = Interner::builder().shards(4).random_salt().build();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        let copy = interner.snapshot();
+        assert_eq!(copy.len(), 2);
+        assert_eq!(copy.stats().entries, 2);
+
+        // The copy has the same keys, and goes its own way from here.
+        let copy_a = copy.intern(String::from("a"));
+        assert_eq!(copy_a.key(), a.key());
+        assert_eq!(copy.len(), 2);
+        drop(copy.intern(String::from("c")));
+        assert!(interner.get("c").is_none());
+        drop(b);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 1);
+        assert!(copy.get("b").is_some());
+    }
+}
@@ -0,0 +1,119 @@
+//! Stable IDs, which stay the same for a value for the life of the interner even if its
+//! entry is dropped and later re-interned. Useful as durable keys for incremental
+//! computation, where a handle can't be held across revisions.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use {BuildKeyHasher, BuildMap, InternKey, Interned, Interner, MapBackend};
+
+/// A dense ID assigned to a key the first time it's interned. IDs count up from 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StableId(pub(crate) u64);
+
+impl StableId {
+    pub fn index(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+pub(crate) struct StableIds<const N: usize> {
+    by_key: HashMap<InternKey<N>, StableId, BuildKeyHasher>,
+    by_id: Vec<InternKey<N>>,
+}
+
+impl<const N: usize> StableIds<N> {
+    pub(crate) fn new() -> Mutex<StableIds<N>> {
+        Mutex::new(StableIds {
+            by_key: HashMap::default(),
+            by_id: Vec::new(),
+        })
+    }
+
+    /// The number of IDs assigned so far.
+    pub(crate) fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    /// The keys assigned IDs from `first` on, in order.
+    pub(crate) fn keys_from(&self, first: usize) -> Vec<InternKey<N>> {
+        self.by_id.get(first..).unwrap_or(&[]).to_vec()
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Called for each new entry. Keys are never forgotten, so a re-interned value gets back
+    /// the ID it had before.
+    pub(crate) fn assign_stable_id(&self, key: &InternKey<N>) {
+        if let Some(ref ids) = self.stable_ids {
+            let mut ids = ids.lock().unwrap();
+            let ids = &mut *ids;
+            if !ids.by_key.contains_key(key) {
+                let id = StableId(ids.by_id.len() as u64);
+                ids.by_key.insert(key.clone(), id);
+                ids.by_id.push(key.clone());
+            }
+        }
+    }
+
+    /// The stable ID of `handle`'s value. Returns `None` unless the interner was built with
+    /// `InternerBuilder::stable_ids`.
+    pub fn stable_id(&self, handle: &Interned<T, S, N>) -> Option<StableId> {
+        let ids = match self.stable_ids {
+            Some(ref ids) => ids.lock().unwrap(),
+            None => return None,
+        };
+        ids.by_key.get(handle.key()).cloned()
+    }
+
+    /// The key that was assigned `id`.
+    pub fn key_of_stable_id(&self, id: StableId) -> Option<InternKey<N>> {
+        let ids = match self.stable_ids {
+            Some(ref ids) => ids.lock().unwrap(),
+            None => return None,
+        };
+        ids.by_id.get(id.0 as usize).cloned()
+    }
+
+    /// Get a handle to the value that was assigned `id`, if it's still interned.
+    pub fn get_by_stable_id<'a>(&'a self, id: StableId) -> Option<Interned<'a, T, S, N>> {
+        // Don't hold the ID table's lock while taking a shard's, since inserts take them the
+        // other way around.
+        self.key_of_stable_id(id).and_then(|key| {
+            let map = self.read(&key).unwrap();
+            map.get(&key).map(|field| self.handle(field))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn ids_survive_reinterning() {
+        let interner = Interner::builder().stable_ids().build();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        let id_a = interner.stable_id(&a).unwrap();
+        let id_b = interner.stable_id(&b).unwrap();
+        assert_eq!((id_a.index(), id_b.index()), (0, 1));
+        assert_eq!(interner.get_by_stable_id(id_b), Some(b.clone()));
+
+        drop(a);
+        interner.reclaim().unwrap();
+        assert!(interner.get_by_stable_id(id_a).is_none());
+        let a = interner.intern(String::from("a"));
+        assert_eq!(interner.stable_id(&a), Some(id_a));
+
+        let plain = Interner::new();
+        assert!(plain.stable_id(&plain.intern(String::from("c"))).is_none());
+    }
+}
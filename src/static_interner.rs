@@ -0,0 +1,72 @@
+//! Interners that can be declared as `static`s.
+
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use Interner;
+
+/// An interner with a `const` constructor, for declaring as a `static`:
+///
+/// ```ignore
+/// static NAMES: StaticInterner<String> = StaticInterner::new();
+///
+/// let name = NAMES.intern(String::from("x"));
+/// ```
+///
+/// The interner itself is built the first time it's used, and derefs to an `Interner`.
+pub struct StaticInterner<T> {
+    interner: OnceLock<Interner<T>>,
+    init: fn() -> Interner<T>,
+}
+
+impl<T> StaticInterner<T> {
+    /// A static interner which will be built with `Interner::new`.
+    pub const fn new() -> StaticInterner<T> {
+        StaticInterner::with_init(Interner::new)
+    }
+
+    /// A static interner which will be built by `init`, eg. to use `Interner::builder`.
+    pub const fn with_init(init: fn() -> Interner<T>) -> StaticInterner<T> {
+        StaticInterner {
+            interner: OnceLock::new(),
+            init: init,
+        }
+    }
+
+    pub fn get(&self) -> &Interner<T> {
+        self.interner.get_or_init(self.init)
+    }
+}
+
+impl<T> Deref for StaticInterner<T> {
+    type Target = Interner<T>;
+
+    fn deref(&self) -> &Interner<T> {
+        self.get()
+    }
+}
+
+impl<T> Default for StaticInterner<T> {
+    fn default() -> StaticInterner<T> {
+        StaticInterner::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StaticInterner;
+    use Interner;
+
+    static PLAIN: StaticInterner<String> = StaticInterner::new();
+    static SHARDED: StaticInterner<Vec<u8>> = StaticInterner::with_init(|| Interner::builder().shards(2).build());
+
+    #[test]
+    fn statics() {
+        let a = PLAIN.intern(String::from("a"));
+        assert_eq!(PLAIN.intern_borrowed("a"), a);
+        assert!(PLAIN.owns(&a));
+        let b = SHARDED.intern(vec![1, 2]);
+        assert_eq!(&b[..], [1, 2]);
+        assert_eq!(SHARDED.shards.len(), 2);
+    }
+}
@@ -0,0 +1,113 @@
+//! Tables of symbols known ahead of time, such as a language's keywords.
+
+/// Define a table of symbols with a constant naming each of them:
+///
+/// ```ignore
+/// static_symbols! {
+///     pub struct Keywords {
+///         KW_FN = "fn",
+///         KW_LET = "let",
+///     }
+/// }
+///
+/// let keywords = Keywords::new(&interner);
+/// if token == keywords[Keywords::KW_LET] { ... }
+/// ```
+///
+/// `Keywords::new` preloads the texts, so their entries are pinned, and holds a handle to
+/// each, keyed up front. Indexing the table with a constant gives that symbol's handle, and
+/// `symbol_of` goes the other way without hashing anything but the handle's key.
+#[macro_export]
+macro_rules! static_symbols {
+    (@consts $index:expr;) => {};
+    (@consts $index:expr; $sym:ident $($rest:ident)*) => {
+        pub const $sym: usize = $index;
+        $crate::static_symbols!(@consts $index + 1; $($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis struct $name:ident { $($sym:ident = $text:expr),* $(,)* }) => {
+        $(#[$attr])*
+        $vis struct $name<'a, S: $crate::BuildMap<String, N> + 'a = $crate::BuildKeyHasher, const N: usize = 20> {
+            handles: Vec<$crate::Interned<'a, String, S, N>>,
+            by_key: ::std::collections::HashMap<$crate::InternKey<N>, usize, $crate::BuildKeyHasher>,
+        }
+
+        // The constants are only defined for the default parameters, so that naming them
+        // doesn't leave the parameters to be inferred.
+        impl $name<'static> {
+            $crate::static_symbols!(@consts 0; $($sym)*);
+
+            /// Each symbol's text, in order.
+            pub const TEXTS: &'static [&'static str] = &[$($text),*];
+        }
+
+        #[allow(dead_code)]
+        impl<'a, S: $crate::BuildMap<String, N> + 'a, const N: usize> $name<'a, S, N> {
+            /// Intern and pin every symbol's text. Panics if the interner has no room for
+            /// them.
+            pub fn new(interner: &'a $crate::Interner<String, S, N>) -> $name<'a, S, N> {
+                if let Err(e) = interner.preload($name::TEXTS.iter().map(|&text| String::from(text))) {
+                    panic!("{}", e);
+                }
+                let handles: Vec<_> = $name::TEXTS.iter().map(|&text| interner.intern_borrowed(text)).collect();
+                let by_key = handles.iter().enumerate().map(|(i, handle)| (handle.key().clone(), i)).collect();
+                $name {
+                    handles: handles,
+                    by_key: by_key,
+                }
+            }
+
+            /// The symbol whose handle is equal to `handle`, if any.
+            pub fn symbol_of(&self, handle: &$crate::Interned<String, S, N>) -> Option<usize> {
+                self.by_key.get(handle.key()).cloned()
+            }
+
+            pub fn len(&self) -> usize {
+                self.handles.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.handles.is_empty()
+            }
+        }
+
+        impl<'a, S: $crate::BuildMap<String, N> + 'a, const N: usize> ::std::ops::Index<usize> for $name<'a, S, N> {
+            type Output = $crate::Interned<'a, String, S, N>;
+
+            fn index(&self, symbol: usize) -> &$crate::Interned<'a, String, S, N> {
+                &self.handles[symbol]
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    static_symbols! {
+        /// Some of Rust's keywords.
+        struct Keywords {
+            KW_FN = "fn",
+            KW_LET = "let",
+            KW_MATCH = "match",
+        }
+    }
+
+    #[test]
+    fn static_symbols() {
+        let interner = Interner::new();
+        let keywords = Keywords::new(&interner);
+        assert_eq!(Keywords::KW_MATCH, 2);
+        assert_eq!(keywords.len(), 3);
+        assert_eq!(&keywords[Keywords::KW_LET][..], "let");
+
+        let token = interner.intern_borrowed("fn");
+        assert!(token == keywords[Keywords::KW_FN]);
+        assert_eq!(keywords.symbol_of(&token), Some(Keywords::KW_FN));
+        assert_eq!(keywords.symbol_of(&interner.intern_borrowed("x")), None);
+
+        drop(keywords);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.len(), 3);
+    }
+}
@@ -0,0 +1,325 @@
+//! Running counts of what an interner holds, including their high-water marks.
+
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeStruct;
+
+use {BuildMap, InternError, InternField, InternKey, Interner, MapBackend};
+
+/// A snapshot of an interner's size and lock contention. Byte counts are approximate: they
+/// cover the entries themselves plus whatever the interner's `heap_size` function reports for
+/// each value.
+///
+/// With the `serde` feature, `Stats` serializes as a flat struct for health reports, with
+/// durations in (fractional) seconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Stats {
+    pub entries: usize,
+    pub peak_entries: usize,
+    pub bytes: usize,
+    pub peak_bytes: usize,
+    /// How many times values have been interned, whether or not they were new.
+    pub interns: u64,
+    /// How many handles have been taken to entries that were already interned.
+    pub hits: u64,
+    /// How many entries have been inserted, by interning or otherwise.
+    pub misses: u64,
+    /// How many unused entries have been evicted, for being stale or to make room.
+    pub evictions: u64,
+    /// How many times a shard's lock was already held and had to be waited for.
+    pub contended: usize,
+    /// The total time spent waiting for shard locks.
+    pub lock_wait: Duration,
+    /// A moving average of the time spent waiting for a contended lock, weighted towards
+    /// recent waits.
+    pub recent_lock_wait: Duration,
+}
+
+impl Stats {
+    /// How many times each entry has been interned, on average: `interns / misses`. 1.0
+    /// means interning hasn't shared anything, and the higher it is the more interning is
+    /// paying for itself. Entries inserted other than by interning, eg. by imports, count
+    /// as unshared. 1.0 if nothing has been inserted.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.misses == 0 {
+            1.0
+        } else {
+            self.interns as f64 / self.misses as f64
+        }
+    }
+}
+
+pub struct Counters {
+    entries: AtomicUsize,
+    peak_entries: AtomicUsize,
+    bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    interns: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    contended: AtomicUsize,
+    lock_wait_nanos: AtomicU64,
+    recent_wait_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub fn new() -> Counters {
+        Counters {
+            entries: AtomicUsize::new(0),
+            peak_entries: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            interns: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            contended: AtomicUsize::new(0),
+            lock_wait_nanos: AtomicU64::new(0),
+            recent_wait_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    pub(crate) fn entry_bytes(&self, field: &InternField<T, N>) -> usize {
+        // The map holds a key and a pointer to the boxed field.
+        let shallow = mem::size_of::<InternKey<N>>() + mem::size_of::<Box<InternField<T, N>>>()
+                    + mem::size_of::<InternField<T, N>>();
+        shallow + self.heap_size.map_or(0, |heap_size| heap_size(&field.data))
+    }
+
+    /// Count `field` before it's inserted, failing if there's no room for it under
+    /// `max_entries` or `max_bytes`. Called with the entry's shard locked, and only if the
+    /// entry is absent, so that the entry is always inserted afterwards.
+    pub(crate) fn admit(&self, field: &InternField<T, N>) -> Result<(), InternError> {
+        let entries = try!(reserve(&self.counters.entries, 1, self.max_entries));
+        let bytes = match reserve(&self.counters.bytes, self.entry_bytes(field), self.max_bytes) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.counters.entries.fetch_sub(1, Relaxed);
+                return Err(e);
+            },
+        };
+        self.counters.peak_entries.fetch_max(entries, Relaxed);
+        self.counters.peak_bytes.fetch_max(bytes, Relaxed);
+        Ok(())
+    }
+
+    /// Whether an entry of `bytes` bytes would fit under the interner's limits right now.
+    pub(crate) fn has_room(&self, bytes: usize) -> bool {
+        let entries_fit = self.max_entries.is_none_or(|max| self.counters.entries.load(Relaxed) < max);
+        let bytes_fit = self.max_bytes.is_none_or(|max| self.counters.bytes.load(Relaxed) + bytes <= max);
+        entries_fit && bytes_fit
+    }
+
+    pub(crate) fn note_intern(&self) {
+        self.counters.interns.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn note_hit(&self) {
+        self.counters.hits.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn note_miss(&self) {
+        self.counters.misses.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn note_evicted(&self, count: usize) {
+        self.counters.evictions.fetch_add(count as u64, Relaxed);
+    }
+
+    pub(crate) fn note_removed(&self, field: &InternField<T, N>) {
+        self.counters.entries.fetch_sub(1, Relaxed);
+        self.counters.bytes.fetch_sub(self.entry_bytes(field), Relaxed);
+    }
+
+    pub(crate) fn note_contended(&self, waited: Duration) {
+        let nanos = waited.as_secs() * 1_000_000_000 + waited.subsec_nanos() as u64;
+        self.counters.contended.fetch_add(1, Relaxed);
+        self.counters.lock_wait_nanos.fetch_add(nanos, Relaxed);
+        // An exponential moving average over roughly the last eight waits. Racing updates
+        // can lose a sample, which is fine for a statistic.
+        let recent = self.counters.recent_wait_nanos.load(Relaxed);
+        let recent = if recent == 0 { nanos } else { recent - recent / 8 + nanos / 8 };
+        self.counters.recent_wait_nanos.store(recent, Relaxed);
+    }
+
+    /// How much sharing interning has achieved. See `Stats::dedup_ratio`.
+    pub fn dedup_ratio(&self) -> f64 {
+        self.stats().dedup_ratio()
+    }
+
+    /// Estimate how many bytes of payload deduplication is currently saving, compared to
+    /// every live handle owning its own copy of its value. That is, the sum over entries of
+    /// `(count - 1) * size`, where `size` counts the value and its `heap_size`.
+    pub fn dedup_savings(&self) -> usize {
+        let mut saved = 0;
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            for field in map.iter() {
+                let count = field.count.load(Relaxed);
+                if count > 1 {
+                    let size = mem::size_of::<T>() + self.heap_size.map_or(0, |heap_size| heap_size(&field.data));
+                    saved += (count - 1) * size;
+                }
+            }
+        }
+        saved
+    }
+
+    /// Get the current and peak sizes of the table, and how much time has been spent waiting
+    /// for its locks. Entries whose handles have all been dropped are counted until they're
+    /// reclaimed.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            entries: self.counters.entries.load(Relaxed),
+            peak_entries: self.counters.peak_entries.load(Relaxed),
+            bytes: self.counters.bytes.load(Relaxed),
+            peak_bytes: self.counters.peak_bytes.load(Relaxed),
+            interns: self.counters.interns.load(Relaxed),
+            hits: self.counters.hits.load(Relaxed),
+            misses: self.counters.misses.load(Relaxed),
+            evictions: self.counters.evictions.load(Relaxed),
+            contended: self.counters.contended.load(Relaxed),
+            lock_wait: Duration::from_nanos(self.counters.lock_wait_nanos.load(Relaxed)),
+            recent_lock_wait: Duration::from_nanos(self.counters.recent_wait_nanos.load(Relaxed)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Stats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut stats = try!(serializer.serialize_struct("Stats", 11));
+        try!(stats.serialize_field("entries", &self.entries));
+        try!(stats.serialize_field("peak_entries", &self.peak_entries));
+        try!(stats.serialize_field("bytes", &self.bytes));
+        try!(stats.serialize_field("peak_bytes", &self.peak_bytes));
+        try!(stats.serialize_field("interns", &self.interns));
+        try!(stats.serialize_field("hits", &self.hits));
+        try!(stats.serialize_field("misses", &self.misses));
+        try!(stats.serialize_field("evictions", &self.evictions));
+        try!(stats.serialize_field("contended", &self.contended));
+        try!(stats.serialize_field("lock_wait", &self.lock_wait.as_secs_f64()));
+        try!(stats.serialize_field("recent_lock_wait", &self.recent_lock_wait.as_secs_f64()));
+        stats.end()
+    }
+}
+
+/// Add `amount` to `counter` unless that would take it past `max`, returning the new total.
+fn reserve(counter: &AtomicUsize, amount: usize, max: Option<usize>) -> Result<usize, InternError> {
+    let max = max.unwrap_or(usize::MAX);
+    counter.fetch_update(Relaxed, Relaxed, |total| total.checked_add(amount).filter(|&total| total <= max))
+           .map(|total| total + amount)
+           .map_err(|_| InternError::CapacityExceeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use Interner;
+
+    #[test]
+    fn peaks() {
+        let interner = Interner::builder().heap_size(|s: &String| s.capacity()).build();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("bbbbbbbb"));
+        let peak = interner.stats();
+        assert_eq!(peak.entries, 2);
+        drop(a);
+        drop(b);
+        interner.reclaim().unwrap();
+        let stats = interner.stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.bytes, 0);
+        assert_eq!(stats.peak_entries, 2);
+        assert_eq!(stats.peak_bytes, peak.bytes);
+        assert!(peak.bytes > 9);
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let _a2 = interner.intern_borrowed("a");
+        let _a3 = a.clone();
+        assert!(interner.get("a").is_some());
+        assert!(interner.get("b").is_none());
+        let stats = interner.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 1, 0));
+        drop(interner.handles_by_key());
+        assert_eq!(interner.stats().hits, 2);
+
+        let b = interner.intern(String::from("b"));
+        interner.advance_epoch();
+        drop(b);
+        interner.sweep_stale().unwrap();
+        assert_eq!(interner.stats().evictions, 1);
+    }
+
+    #[test]
+    fn dedup_ratio() {
+        let interner = Interner::new();
+        assert_eq!(interner.dedup_ratio(), 1.0);
+        let _a = interner.intern(String::from("a"));
+        let _b = interner.intern_borrowed("b");
+        for _ in 0..3 {
+            interner.intern_borrowed("a");
+        }
+        assert!(interner.get("b").is_some());
+        assert_eq!(interner.stats().interns, 5);
+        assert_eq!(interner.dedup_ratio(), 2.5);
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn serialize() {
+        use serde_json::{self, Value};
+
+        let interner = Interner::new();
+        let _a = interner.intern(String::from("a"));
+        let stats: Value = serde_json::to_value(interner.stats()).unwrap();
+        assert_eq!(stats["entries"], 1);
+        assert_eq!(stats["misses"], 1);
+        assert_eq!(stats["lock_wait"], 0.0);
+    }
+
+    #[test]
+    fn contention() {
+        let interner = Interner::builder().shards(1).build();
+        interner.intern(String::from("a"));
+        assert_eq!(interner.stats().contended, 0);
+        thread::scope(|scope| {
+            let map = interner.lock_shard(0).unwrap();
+            let reader = scope.spawn(|| interner.get("a").is_some());
+            thread::sleep(Duration::from_millis(50));
+            drop(map);
+            assert!(reader.join().unwrap());
+        });
+        let stats = interner.stats();
+        assert_eq!(stats.contended, 1);
+        assert!(stats.lock_wait >= Duration::from_millis(40));
+        assert_eq!(stats.recent_lock_wait, stats.lock_wait);
+    }
+
+    #[test]
+    fn dedup_savings() {
+        let interner = Interner::builder().heap_size(|v: &Vec<u64>| v.capacity() * 8).build();
+        let a = interner.intern(vec![1u64, 2, 3, 4]);
+        assert_eq!(interner.dedup_savings(), 0);
+        let handles = vec![a.clone(), a.clone(), interner.intern(vec![1u64, 2, 3, 4])];
+        let size = ::std::mem::size_of::<Vec<u64>>() + a.capacity() * 8;
+        assert_eq!(interner.dedup_savings(), 3 * size);
+        drop(handles);
+        assert_eq!(interner.dedup_savings(), 0);
+    }
+}
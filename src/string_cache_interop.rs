@@ -0,0 +1,45 @@
+//! Conversions between interned strings and `string_cache` atoms, for sharing strings with
+//! code that already uses atoms.
+
+use string_cache::{Atom, DefaultAtom, StaticAtomSet};
+
+use {BuildMap, Interned, Interner};
+
+impl<S: BuildMap<String, N>, const N: usize> Interner<String, S, N> {
+    /// Intern the string behind `atom`.
+    pub fn intern_atom<'a, Static: StaticAtomSet>(&'a self, atom: &Atom<Static>) -> Interned<'a, String, S, N> {
+        self.intern_borrowed(&**atom)
+    }
+}
+
+impl<'a, S: BuildMap<String, N>, const N: usize> Interned<'a, String, S, N> {
+    /// The atom for this string. Atoms are interned separately, so this looks the string up
+    /// in (or adds it to) `string_cache`'s own table.
+    pub fn to_atom<Static: StaticAtomSet>(&self) -> Atom<Static> {
+        Atom::from(&self[..])
+    }
+}
+
+impl<'a, 'b, S: BuildMap<String, N>, const N: usize> From<&'b Interned<'a, String, S, N>> for DefaultAtom {
+    fn from(interned: &'b Interned<'a, String, S, N>) -> DefaultAtom {
+        interned.to_atom()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use string_cache::DefaultAtom;
+
+    use Interner;
+
+    #[test]
+    fn atoms() {
+        let interner = Interner::new();
+        let atom = DefaultAtom::from("div");
+        let div = interner.intern_atom(&atom);
+        assert_eq!(&div[..], "div");
+        assert_eq!(DefaultAtom::from(&div), atom);
+        let to_atom: DefaultAtom = div.to_atom();
+        assert_eq!(interner.intern_atom(&to_atom), div);
+    }
+}
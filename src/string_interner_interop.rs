@@ -0,0 +1,47 @@
+//! Lets the keys of this crate stand in for `string-interner` symbols, so that they can be
+//! used with code written against its generic `Symbol` bound.
+
+use string_interner::Symbol;
+
+use {Spur, StableId};
+
+impl Symbol for Spur {
+    fn try_from_usize(index: usize) -> Option<Spur> {
+        Spur::try_from_usize(index)
+    }
+
+    fn to_usize(self) -> usize {
+        self.into_usize()
+    }
+}
+
+impl Symbol for StableId {
+    fn try_from_usize(index: usize) -> Option<StableId> {
+        Some(StableId(index as u64))
+    }
+
+    fn to_usize(self) -> usize {
+        self.index() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use string_interner::Symbol;
+
+    use Interner;
+
+    fn round_trip<S: Symbol>(symbol: S) -> S {
+        S::try_from_usize(symbol.to_usize()).unwrap()
+    }
+
+    #[test]
+    fn symbols() {
+        let interner = Interner::builder().stable_ids().build();
+        let mut rodeo = interner.rodeo();
+        let a = rodeo.get_or_intern("a");
+        assert_eq!(round_trip(a), a);
+        let id = interner.stable_id(&interner.intern(String::from("a"))).unwrap();
+        assert_eq!(round_trip(id), id);
+    }
+}
@@ -0,0 +1,154 @@
+//! Dense symbols. Unlike `StableId`s, a symbol's slot is reused once its entry is removed, so
+//! each slot carries a generation that's bumped on reuse to catch stale symbols.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use {BuildKeyHasher, BuildMap, InternError, InternKey, Interned, Interner, MapBackend};
+
+/// A small copyable name for an interned entry. Resolving it after the entry has been
+/// removed, or in a different interner, fails rather than yielding whatever now occupies
+/// its slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol {
+    index: u32,
+    generation: u32,
+    interner: usize,
+}
+
+impl Symbol {
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+struct Slot<const N: usize> {
+    generation: u32,
+    key: Option<InternKey<N>>,
+}
+
+pub(crate) struct Symbols<const N: usize> {
+    slots: Vec<Slot<N>>,
+    free: Vec<u32>,
+    by_key: HashMap<InternKey<N>, u32, BuildKeyHasher>,
+}
+
+impl<const N: usize> Symbols<N> {
+    pub(crate) fn new() -> Mutex<Symbols<N>> {
+        Mutex::new(Symbols {
+            slots: Vec::new(),
+            free: Vec::new(),
+            by_key: HashMap::default(),
+        })
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Called for each new entry.
+    pub(crate) fn assign_symbol(&self, key: &InternKey<N>) {
+        if let Some(ref symbols) = self.symbols {
+            let mut symbols = symbols.lock().unwrap();
+            let symbols = &mut *symbols;
+            let index = match symbols.free.pop() {
+                Some(index) => index,
+                None => {
+                    symbols.slots.push(Slot {
+                        generation: 0,
+                        key: None,
+                    });
+                    (symbols.slots.len() - 1) as u32
+                },
+            };
+            symbols.slots[index as usize].key = Some(key.clone());
+            symbols.by_key.insert(key.clone(), index);
+        }
+    }
+
+    /// Called for each removed entry.
+    pub(crate) fn free_symbol(&self, key: &InternKey<N>) {
+        if let Some(ref symbols) = self.symbols {
+            let mut symbols = symbols.lock().unwrap();
+            if let Some(index) = symbols.by_key.remove(key) {
+                let slot = &mut symbols.slots[index as usize];
+                slot.key = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                symbols.free.push(index);
+            }
+        }
+    }
+
+    /// The symbol of `handle`'s entry. Returns `None` unless the interner was built with
+    /// `InternerBuilder::symbols`.
+    pub fn symbol(&self, handle: &Interned<T, S, N>) -> Option<Symbol> {
+        let symbols = match self.symbols {
+            Some(ref symbols) => symbols.lock().unwrap(),
+            None => return None,
+        };
+        symbols.by_key.get(handle.key()).map(|&index| {
+            Symbol {
+                index: index,
+                generation: symbols.slots[index as usize].generation,
+                interner: self.id,
+            }
+        })
+    }
+
+    /// Get a handle to the entry named by `symbol`. Fails with `InternError::Stale` if the
+    /// entry has since been removed, or `InternError::Foreign` if `symbol` came from another
+    /// interner.
+    pub fn resolve<'a>(&'a self, symbol: Symbol) -> Result<Interned<'a, T, S, N>, InternError> {
+        if symbol.interner != self.id {
+            return Err(InternError::Foreign);
+        }
+        let key = {
+            let symbols = match self.symbols {
+                Some(ref symbols) => symbols.lock().unwrap(),
+                None => return Err(InternError::Stale),
+            };
+            match symbols.slots.get(symbol.index as usize) {
+                Some(&Slot { generation, key: Some(ref key) }) if generation == symbol.generation => key.clone(),
+                _ => return Err(InternError::Stale),
+            }
+        };
+        // The entry may have been removed since the symbol table's lock was released.
+        let map = try!(self.read(&key));
+        map.get(&key).map(|field| self.handle(field)).ok_or(InternError::Stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {InternError, Interner};
+
+    #[test]
+    fn stale_symbols() {
+        let interner = Interner::builder().symbols().build();
+        let a = interner.intern(String::from("a"));
+        let sym_a = interner.symbol(&a).unwrap();
+        assert_eq!(interner.resolve(sym_a).unwrap(), a);
+
+        drop(a);
+        interner.reclaim().unwrap();
+        let b = interner.intern(String::from("b"));
+        let sym_b = interner.symbol(&b).unwrap();
+        assert_eq!(sym_b.index(), sym_a.index());
+        assert!(interner.resolve(sym_a).err() == Some(InternError::Stale));
+        assert_eq!(interner.resolve(sym_b).unwrap(), b);
+    }
+
+    #[test]
+    fn foreign_symbols() {
+        let a = Interner::builder().symbols().build();
+        let b = Interner::builder().symbols().build();
+        let in_a = a.intern(String::from("x"));
+        let in_b = b.intern(String::from("x"));
+        assert!(a.owns(&in_a) && !a.owns(&in_b));
+        let sym = a.symbol(&in_a).unwrap();
+        assert!(sym != b.symbol(&in_b).unwrap());
+        assert!(b.resolve(sym).err() == Some(InternError::Foreign));
+    }
+}
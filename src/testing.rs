@@ -0,0 +1,102 @@
+//! `quickcheck` support for property-testing code that holds `Interned` handles.
+//!
+//! Interners can't be generated directly since their handles borrow them, so instead this
+//! generates scripts of operations that can be replayed against a fresh interner.
+
+use std::hash::Hash;
+
+use quickcheck::{Arbitrary, Gen};
+
+use {BuildMap, Interned, Interner};
+
+/// A single operation on an interner and the handles taken from it so far. Handle indices
+/// are taken modulo the number of live handles.
+#[derive(Clone, Debug)]
+pub enum Op<T> {
+    Intern(T),
+    Clone(usize),
+    Drop(usize),
+    Reclaim,
+}
+
+/// A sequence of operations to replay against an interner with `apply`.
+#[derive(Clone, Debug)]
+pub struct Ops<T> {
+    pub ops: Vec<Op<T>>,
+}
+
+impl<T: Hash + Clone> Ops<T> {
+    /// Run the operations against `interner`, returning the handles that are still alive.
+    pub fn apply<'a, S: BuildMap<T, N>, const N: usize>(&self, interner: &'a Interner<T, S, N>) -> Vec<Interned<'a, T, S, N>>
+            where T: 'a
+    {
+        let mut handles = Vec::new();
+        for op in &self.ops {
+            match *op {
+                Op::Intern(ref data) => handles.push(interner.intern(data.clone())),
+                Op::Clone(i) => if !handles.is_empty() {
+                    let handle = handles[i % handles.len()].clone();
+                    handles.push(handle);
+                },
+                Op::Drop(i) => if !handles.is_empty() {
+                    let len = handles.len();
+                    drop(handles.swap_remove(i % len));
+                },
+                Op::Reclaim => interner.reclaim().unwrap(),
+            }
+        }
+        handles
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Op<T> {
+    fn arbitrary(g: &mut Gen) -> Op<T> {
+        match u8::arbitrary(g) % 8 {
+            0 | 1 | 2 | 3 => Op::Intern(T::arbitrary(g)),
+            4 | 5 => Op::Clone(usize::arbitrary(g)),
+            6 => Op::Drop(usize::arbitrary(g)),
+            _ => Op::Reclaim,
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=Op<T>>> {
+        match *self {
+            Op::Intern(ref data) => Box::new(data.shrink().map(Op::Intern)),
+            _ => Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Ops<T> {
+    fn arbitrary(g: &mut Gen) -> Ops<T> {
+        Ops {
+            ops: Vec::arbitrary(g),
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=Ops<T>>> {
+        Box::new(self.ops.shrink().map(|ops| Ops { ops: ops }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck::quickcheck;
+
+    use super::Ops;
+    use Interner;
+
+    fn live_handles_resolve(ops: Ops<u8>) -> bool {
+        let interner = Interner::new();
+        let handles = ops.apply(&interner);
+        let ok = handles.iter().all(|handle| interner.get(&**handle).as_ref() == Some(handle));
+        drop(handles);
+        interner.reclaim().unwrap();
+        ok && interner.is_empty()
+    }
+
+    #[test]
+    fn replay() {
+        quickcheck(live_handles_resolve as fn(Ops<u8>) -> bool);
+    }
+}
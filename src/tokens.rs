@@ -0,0 +1,130 @@
+//! Interning many slices of one source string at once, as a lexer does.
+
+use std::ops::Range;
+
+use {BuildMap, InternError, InternField, Interned, Interner, MapBackend};
+
+impl<S: BuildMap<String, N>, const N: usize> Interner<String, S, N> {
+    /// Intern the slice of `source` under each of `spans`, returning their handles in order.
+    /// The keys are all derived before any lock is taken, then each shard's lock is taken at
+    /// most once for all of the tokens in it, unless it has to be let go while room is made
+    /// under the interner's limits. A string is only allocated for a token that isn't
+    /// interned yet, and only once however often it repeats.
+    ///
+    /// Panics if a span isn't a valid range of `source`, or if the interner fills up.
+    pub fn intern_tokens<'a, I>(&'a self, source: &str, spans: I) -> Vec<Interned<'a, String, S, N>>
+            where I: IntoIterator<Item=Range<usize>>
+    {
+        match self.try_intern_tokens(source, spans) {
+            Ok(handles) => handles,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `intern_tokens`, but returns an error rather than panicking if the interner fills
+    /// up or a lock is poisoned. Tokens already interned under the same key are compared, as
+    /// by `try_intern`. On an error no handles are kept, though tokens interned before it
+    /// stay in the table until they're reclaimed.
+    pub fn try_intern_tokens<'a, I>(&'a self, source: &str, spans: I)
+            -> Result<Vec<Interned<'a, String, S, N>>, InternError>
+            where I: IntoIterator<Item=Range<usize>>
+    {
+        let mut tokens: Vec<_> = spans.into_iter()
+                                      .enumerate()
+                                      .map(|(i, span)| {
+                                          let token = &source[span];
+                                          let key = self.key_of(token);
+                                          (self.shard_index(&key), i, key, token)
+                                      })
+                                      .collect();
+        tokens.sort_by_key(|&(index, i, _, _)| (index, i));
+
+        let mut handles: Vec<Option<Interned<'a, String, S, N>>> = tokens.iter().map(|_| None).collect();
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(index) = tokens.peek().map(|&(index, _, _, _)| index) {
+            let mut map = try!(self.lock_shard(index));
+            while let Some((_, i, key, token)) = tokens.next_if(|&(next, _, _, _)| next == index) {
+                self.note_intern();
+                let mut made_room = false;
+                loop {
+                    if let Some(field) = map.get(&key) {
+                        if field.data != token {
+                            drop(map);
+                            return Err(InternError::Collision);
+                        }
+                        handles[i] = Some(self.handle(field));
+                        break;
+                    }
+                    let field = Box::new(InternField::new(key.clone(), token.to_owned()));
+                    match self.admit(&field) {
+                        Ok(()) => {
+                            let (field, _) = map.get_or_insert(field);
+                            self.inserted(field);
+                            handles[i] = Some(self.new_handle(field));
+                            break;
+                        },
+                        // Making room takes shard locks, so let go of this one while it
+                        // happens, then look again since the token may have been interned
+                        // in the meantime.
+                        Err(InternError::CapacityExceeded) if !made_room => {
+                            drop(map);
+                            self.make_room(self.entry_bytes(&field));
+                            made_room = true;
+                            map = try!(self.lock_shard(index));
+                        },
+                        Err(e) => {
+                            drop(map);
+                            return Err(e);
+                        },
+                    }
+                }
+            }
+        }
+        self.maybe_reclaim();
+        Ok(handles.into_iter().map(Option::unwrap).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::Relaxed;
+
+    use {InternError, Interner};
+
+    #[test]
+    fn intern_tokens() {
+        let interner = Interner::builder().shards(4).build();
+        let x = interner.intern(String::from("x"));
+        let source = "let x = x + 1";
+        let spans = vec![0..3, 4..5, 6..7, 8..9, 10..11, 12..13];
+        let tokens = interner.intern_tokens(source, spans);
+        let strs: Vec<&str> = tokens.iter().map(|token| &token[..]).collect();
+        assert_eq!(strs, ["let", "x", "=", "x", "+", "1"]);
+        assert_eq!(tokens[1], x);
+        assert_eq!(tokens[3], x);
+        assert_eq!(interner.len(), 5);
+        assert_eq!(interner.intern_borrowed("let"), tokens[0]);
+    }
+
+    #[test]
+    fn try_intern_tokens() {
+        let interner = Interner::builder().shards(1).max_entries(2).build();
+        let a = interner.intern(String::from("a"));
+        assert_eq!(interner.try_intern_tokens("a b c", vec![0..1, 2..3, 4..5]).err(),
+                   Some(InternError::CapacityExceeded));
+        // The handles to "a" and "b" were given back.
+        assert_eq!(a.field.count.load(Relaxed), 1);
+        assert_eq!(interner.get("b").unwrap().field.count.load(Relaxed), 1);
+        assert!(interner.get("c").is_none());
+    }
+
+    #[test]
+    fn intern_tokens_makes_room() {
+        let interner = Interner::builder().shards(1).max_entries(2).build();
+        drop(interner.intern(String::from("a")));
+        drop(interner.intern(String::from("b")));
+        let tokens = interner.intern_tokens("c d", vec![0..1, 2..3]);
+        assert_eq!(*tokens[1], "d");
+        assert_eq!(interner.len(), 2);
+    }
+}
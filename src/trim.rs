@@ -0,0 +1,135 @@
+//! Shedding unused entries under memory pressure, for long-lived servers whose interners
+//! double as caches.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering::{Acquire, Relaxed};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use {BuildMap, EvictionReason, InternError, InternKey, Interner, MapBackend};
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Remove unused entries, least recently used first, until the interner's estimated size
+    /// (see `Stats::bytes`) is at most `target_bytes`. Entries are aged by the epoch they were
+    /// last used in, so this is only as fine-grained as the interner's epochs. Returns how
+    /// many entries were removed, besides those already waiting to be reclaimed. Entries with
+    /// handles are never removed, so the target may not be met.
+    pub fn trim(&self, target_bytes: usize) -> Result<usize, InternError> {
+        drop(try!(self.reclaim_retired()));
+        let mut excess = match self.stats().bytes.checked_sub(target_bytes) {
+            Some(excess) if excess > 0 => excess,
+            _ => return Ok(0),
+        };
+
+        let mut unused: Vec<(u64, InternKey<N>, usize)> = Vec::new();
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            for field in map.iter().filter(|field| 0 == field.count.load(Acquire)) {
+                unused.push((field.epoch.load(Relaxed), field.key.clone(), self.entry_bytes(field)));
+            }
+        }
+        unused.sort();
+        let mut victims = Vec::new();
+        for (_, key, bytes) in unused {
+            if excess == 0 {
+                break;
+            }
+            excess = excess.saturating_sub(bytes);
+            victims.push(key);
+        }
+        let removed = try!(self.remove_unused(victims));
+        let count = removed.len();
+        self.evicted(removed, EvictionReason::Pressure);
+        Ok(count)
+    }
+
+    /// Start a thread which calls `pressure` every `period` and, whenever it returns a
+    /// budget, trims the interner down to it. `pressure` is where a server hooks in whatever
+    /// tells it memory is short, eg. a cgroup's usage or an allocator's statistics. The
+    /// thread stops when the returned `Trimmer` is dropped.
+    pub fn spawn_trimmer<F>(this: &Arc<Interner<T, S, N>>, period: Duration, mut pressure: F) -> Trimmer
+            where F: FnMut() -> Option<usize> + Send + 'static,
+                  Interner<T, S, N>: Send + Sync + 'static
+    {
+        let interner = this.clone();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(period) {
+                if let Some(target) = pressure() {
+                    // A poisoned lock will be reported to whoever interns next.
+                    let _ = interner.trim(target);
+                }
+            }
+        });
+        Trimmer {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// A background thread started by `Interner::spawn_trimmer`. Dropping it stops the thread
+/// and waits for it to finish.
+pub struct Trimmer {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Trimmer {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use {EvictionReason, Interner};
+
+    #[test]
+    fn trim() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let log = evicted.clone();
+        let interner = Interner::builder()
+                                .mark_and_sweep()
+                                .on_evict(move |s: String, reason| log.lock().unwrap().push((s, reason)))
+                                .build();
+        drop(interner.intern(String::from("old")));
+        interner.advance_epoch();
+        drop(interner.intern(String::from("new")));
+        let kept = interner.intern(String::from("kept"));
+        let per_entry = interner.stats().bytes / 3;
+
+        assert_eq!(interner.trim(3 * per_entry).unwrap(), 0);
+        assert_eq!(interner.trim(2 * per_entry).unwrap(), 1);
+        assert_eq!(*evicted.lock().unwrap(), [(String::from("old"), EvictionReason::Pressure)]);
+        assert_eq!(interner.trim(0).unwrap(), 1);
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.get("kept"), Some(kept));
+    }
+
+    #[test]
+    fn trimmer() {
+        let interner = Arc::new(Interner::builder().mark_and_sweep().build());
+        drop(interner.intern(String::from("a")));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let trimmer = Interner::spawn_trimmer(&interner, Duration::from_millis(1), move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+            Some(0)
+        });
+        while calls.load(Ordering::Relaxed) < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(trimmer);
+        assert!(interner.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! UUIDs for keys, for storage layers which are keyed by UUID.
+
+use uuid::Uuid;
+
+use {BuildMap, InternKey, Interned, Interner, MapBackend};
+
+// The UUID bits that hold the key, around the version (bits 76 to 79) and the variant (bits
+// 62 and 63).
+const HIGH: u128 = !0 << 80;
+const MID: u128 = 0xfff << 64;
+const LOW: u128 = (1 << 62) - 1;
+
+impl<const N: usize> InternKey<N> {
+    /// A version 8 (custom) UUID holding the key's first 122 bits, zero-padded if the key is
+    /// narrower, in the bits the version and variant leave free. A key narrower than 16 bytes
+    /// fits whole and can be read back with `from_uuid`. Wider keys lose their remaining
+    /// bits, but the UUID of a key is always the same.
+    pub fn to_uuid(&self) -> Uuid {
+        let mut bytes = [0; 16];
+        let n = if N < 16 { N } else { 16 };
+        bytes[..n].copy_from_slice(&self.data[..n]);
+        let bits = u128::from_be_bytes(bytes);
+        let packed = (bits & HIGH) | ((bits >> 4) & MID) | ((bits >> 6) & LOW);
+        Uuid::new_v8(packed.to_be_bytes())
+    }
+
+    /// Read a key back from `to_uuid`. Returns `None` for keys 16 bytes or wider, whose UUIDs
+    /// don't hold all of them, or if `uuid` isn't the UUID of any key this wide.
+    pub fn from_uuid(uuid: &Uuid) -> Option<InternKey<N>> {
+        let packed = u128::from_be_bytes(*uuid.as_bytes());
+        if N > 15 || (packed >> 76) & 0xf != 8 || (packed >> 62) & 0b11 != 0b10 {
+            return None;
+        }
+        let bits = (packed & HIGH) | ((packed & MID) << 4) | ((packed & LOW) << 6);
+        let bytes = bits.to_be_bytes();
+        if bytes[N..].iter().any(|&byte| byte != 0) {
+            return None;
+        }
+        let mut data = [0; N];
+        data.copy_from_slice(&bytes[..N]);
+        Some(InternKey::from_bytes(data))
+    }
+}
+
+impl<'a, T, S: BuildMap<T, N>, const N: usize> Interned<'a, T, S, N> {
+    /// The UUID of the handle's key. See `InternKey::to_uuid`.
+    pub fn to_uuid(&self) -> Uuid {
+        self.key().to_uuid()
+    }
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Get a handle to the entry whose key has the UUID `uuid`. Keys narrower than 16 bytes
+    /// are read back out of the UUID with `InternKey::from_uuid` and looked up directly. Wider
+    /// keys don't fit in a UUID, so for them this searches every shard, and code which does
+    /// it often should keep its own map from UUIDs to keys or handles. If several keys share
+    /// a UUID, which would take a 122-bit collision, any one of them may be returned.
+    pub fn get_by_uuid<'a>(&'a self, uuid: &Uuid) -> Option<Interned<'a, T, S, N>>
+            where T: 'a
+    {
+        if N <= 15 {
+            return InternKey::from_uuid(uuid).and_then(|key| {
+                let map = self.read(&key).unwrap();
+                map.get(&key).map(|field| self.handle(field))
+            });
+        }
+        for shard in self.shards.iter() {
+            let map = shard.read().unwrap();
+            let found = map.iter().find(|field| field.key.to_uuid() == *uuid).map(|field| self.handle(field));
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use {InternKey, Interner};
+
+    #[test]
+    fn uuids() {
+        let interner = Interner::new();
+        let a = interner.intern(String::from("a"));
+        let b = interner.intern(String::from("b"));
+        let uuid = a.to_uuid();
+        assert_eq!(uuid.get_version_num(), 8);
+        assert_eq!(&uuid.as_bytes()[..6], &a.key().to_bytes()[..6]);
+        assert!(uuid != b.to_uuid());
+        assert_eq!(interner.get_by_uuid(&uuid), Some(a.clone()));
+        drop(b);
+        interner.reclaim().unwrap();
+        assert_eq!(interner.get_by_uuid(&uuid), Some(a));
+        assert!(interner.get_by_uuid(&Interner::<String>::new().intern(String::from("b")).to_uuid()).is_none());
+        assert_eq!(InternKey::<20>::from_uuid(&uuid), None);
+    }
+
+    #[test]
+    fn narrow_keys_round_trip() {
+        let interner = Interner::builder_with_key_bytes::<12>().build();
+        let a = interner.intern(String::from("a"));
+        let uuid = a.to_uuid();
+        assert_eq!(uuid.get_version_num(), 8);
+        assert_eq!(InternKey::from_uuid(&uuid).as_ref(), Some(a.key()));
+        assert_eq!(interner.get_by_uuid(&uuid), Some(a));
+
+        let narrow = InternKey::<8>::from_bytes([0xff; 8]);
+        assert_eq!(InternKey::from_uuid(&narrow.to_uuid()), Some(narrow));
+        assert_eq!(InternKey::<8>::from_uuid(&Uuid::from_bytes([0xff; 16])), None);
+        assert_eq!(InternKey::<8>::from_uuid(&InternKey::<12>::from_bytes([0xff; 12]).to_uuid()), None);
+    }
+}
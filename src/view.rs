@@ -0,0 +1,93 @@
+//! Read-only access to a whole interner for queries the interner doesn't provide itself.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::sync::RwLockReadGuard;
+use std::sync::atomic::Ordering::Acquire;
+
+use {BuildKeyHasher, BuildMap, InternError, InternKey, Interner, MapBackend};
+
+/// A consistent read-only view of an interner's entries, passed to the closure given to
+/// `Interner::with_read`.
+pub struct InternerView<'a, T: 'a, S: BuildMap<T, N> + 'a = BuildKeyHasher, const N: usize = 20> {
+    interner: &'a Interner<T, S, N>,
+    maps: Vec<RwLockReadGuard<'a, S::Map>>,
+}
+
+impl<T, S: BuildMap<T, N>, const N: usize> Interner<T, S, N> {
+    /// Call `f` with a view of every entry, holding every shard's read lock while it runs so
+    /// that nothing is added or removed in the meantime, eg. to count entries over a certain
+    /// size. `f` mustn't call back into the interner at all: interning would deadlock, and
+    /// so can looking values up, since a read lock that's already held isn't guaranteed to
+    /// be granted again while a writer is waiting. Look values up with the view's `get`
+    /// instead, which uses the locks the view already holds.
+    pub fn with_read<R, F>(&self, f: F) -> Result<R, InternError>
+            where F: FnOnce(&InternerView<T, S, N>) -> R
+    {
+        let mut maps = Vec::with_capacity(self.shards.len());
+        for index in 0..self.shards.len() {
+            maps.push(try!(self.read_shard(index)));
+        }
+        let view = InternerView {
+            interner: self,
+            maps: maps,
+        };
+        Ok(f(&view))
+    }
+}
+
+impl<'a, T: 'a, S: BuildMap<T, N> + 'a, const N: usize> InternerView<'a, T, S, N> {
+    /// Every entry's key, number of handles and value, in no particular order. Entries with
+    /// no handles left that haven't been reclaimed yet are included. Handles can still be
+    /// cloned and dropped while the view exists, so counts can be out of date.
+    pub fn iter<'v>(&'v self) -> Box<dyn Iterator<Item=(&'v InternKey<N>, usize, &'v T)> + 'v> {
+        Box::new(self.maps.iter()
+                          .flat_map(|map| map.iter())
+                          .map(|field| (&field.key, field.count.load(Acquire), &field.data)))
+    }
+
+    /// The number of handles to, and the value of, the entry equal to `data`.
+    pub fn get<B: ?Sized + Hash>(&self, data: &B) -> Option<(usize, &T)>
+            where T: Borrow<B>
+    {
+        let key = self.interner.key_of(data);
+        let map = &self.maps[self.interner.shard_index(&key)];
+        map.get(&key).map(|field| (field.count.load(Acquire), &field.data))
+    }
+
+    /// The number of entries in the interner.
+    pub fn len(&self) -> usize {
+        self.maps.iter().map(|map| map.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Interner;
+
+    #[test]
+    fn with_read() {
+        let interner = Interner::builder().shards(4).mark_and_sweep().build();
+        let big = interner.intern("x".repeat(2000));
+        drop(interner.intern("y".repeat(3000)));
+        let _small = interner.intern(String::from("z"));
+
+        let large = interner.with_read(|view| {
+            view.iter().filter(|&(_, _, s)| s.len() > 1024).count()
+        }).unwrap();
+        assert_eq!(large, 2);
+        let held = interner.with_read(|view| {
+            view.iter().filter(|&(_, count, s)| count > 0 && s.len() > 1024).count()
+        }).unwrap();
+        assert_eq!(held, 1);
+        interner.with_read(|view| {
+            assert_eq!(view.len(), 3);
+            assert_eq!(view.get(&big[..]), Some((1, &*big)));
+            assert_eq!(view.get("w"), None);
+        }).unwrap();
+    }
+}